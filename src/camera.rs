@@ -0,0 +1,265 @@
+use rand::Rng;
+
+use crate::{position::Position, ray::Ray};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ProjectionMode {
+    Perspective,
+    Orthographic,
+    Fisheye,
+    Equirectangular,
+}
+
+impl ProjectionMode {
+    pub fn to_string(&self) -> &str {
+        return match *self {
+            Self::Perspective => "Perspective",
+            Self::Orthographic => "Orthographic",
+            Self::Fisheye => "Fisheye",
+            Self::Equirectangular => "Equirectangular",
+        };
+    }
+
+    pub fn from_string(string: &str) -> Self {
+        return match string {
+            "Perspective" => Self::Perspective,
+            "Orthographic" => Self::Orthographic,
+            "Fisheye" => Self::Fisheye,
+            "Equirectangular" => Self::Equirectangular,
+            _ => Self::Perspective,
+        };
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Camera {
+    pub origin: Position,
+    pub forward: Position,
+    pub right: Position,
+    pub down: Position,
+    half_width: f64,
+    half_height: f64,
+    aspect_ratio: f64,
+    pub aperture_radius: f64,
+    pub focus_distance: f64,
+    pub projection: ProjectionMode,
+    pub orthographic_height: f64,
+    pub fisheye_fov: f64,
+}
+
+impl Camera {
+    pub fn new(
+        look_from: Position,
+        look_at: Position,
+        up: Position,
+        vertical_fov: f64,
+        aspect_ratio: f64,
+        roll: f64,
+        aperture_radius: f64,
+        focus_distance: f64,
+    ) -> Camera {
+        let forward: Position = (look_at - look_from).normalized();
+
+        // Looking straight along the up vector, any horizontal right vector will do
+        let mut right: Position = forward.cross(&up);
+        if right.length() < 1e-9 {
+            right = forward.cross(&Position {
+                x: 0.,
+                y: 1.,
+                z: 0.,
+            });
+        }
+        right = right.normalized();
+        let down: Position = forward.cross(&right);
+
+        let (sin, cos) = roll.sin_cos();
+        let half_height: f64 = (vertical_fov / 2.).tan();
+
+        return Camera {
+            origin: look_from,
+            forward: forward,
+            right: right.scaled(cos) + down.scaled(sin),
+            down: down.scaled(cos) - right.scaled(sin),
+            half_width: half_height * aspect_ratio,
+            half_height: half_height,
+            aspect_ratio: aspect_ratio,
+            aperture_radius: aperture_radius,
+            focus_distance: focus_distance,
+            projection: ProjectionMode::Perspective,
+            orthographic_height: 1.,
+            fisheye_fov: std::f64::consts::PI,
+        };
+    }
+
+    pub fn with_projection(
+        mut self,
+        projection: ProjectionMode,
+        orthographic_height: f64,
+        fisheye_fov: f64,
+    ) -> Camera {
+        self.projection = projection;
+        self.orthographic_height = orthographic_height;
+        self.fisheye_fov = fisheye_fov;
+        return self;
+    }
+
+    // Camera in between two others, used to blur the camera motion over the shutter interval
+    pub fn lerp(from: &Camera, to: &Camera, ratio: f64) -> Camera {
+        let mut camera: Camera = *to;
+        camera.origin = from.origin + (to.origin - from.origin).scaled(ratio);
+        camera.forward = (from.forward + (to.forward - from.forward).scaled(ratio)).normalized();
+        camera.right = (from.right + (to.right - from.right).scaled(ratio)).normalized();
+        camera.down = (from.down + (to.down - from.down).scaled(ratio)).normalized();
+        return camera;
+    }
+
+    // Where the ray starts relative to the camera origin, only the orthographic projection moves it
+    pub fn get_origin_offset(&self, s: f64, t: f64) -> Position {
+        return match self.projection {
+            ProjectionMode::Orthographic => {
+                let half_height: f64 = self.orthographic_height / 2.;
+                self.right
+                    .scaled((2. * s - 1.) * half_height * self.aspect_ratio)
+                    + self.down.scaled((2. * t - 1.) * half_height)
+            }
+            _ => Position {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+        };
+    }
+
+    // s and t go from 0 to 1, left to right and top to bottom of the image
+    pub fn get_direction(&self, s: f64, t: f64) -> Position {
+        return match self.projection {
+            ProjectionMode::Perspective => {
+                self.forward
+                    + self.right.scaled((2. * s - 1.) * self.half_width)
+                    + self.down.scaled((2. * t - 1.) * self.half_height)
+            }
+            ProjectionMode::Orthographic => self.forward,
+            ProjectionMode::Fisheye => {
+                // Equidistant: the angle from the forward axis grows linearly with the radius
+                let x: f64 = (2. * s - 1.) * self.aspect_ratio;
+                let y: f64 = 2. * t - 1.;
+                let radius: f64 = (x * x + y * y).sqrt();
+                if radius < 1e-9 {
+                    return self.forward;
+                }
+                let angle: f64 = radius * self.fisheye_fov / 2.;
+
+                self.forward.scaled(angle.cos())
+                    + (self.right.scaled(x / radius) + self.down.scaled(y / radius))
+                        .scaled(angle.sin())
+            }
+            ProjectionMode::Equirectangular => {
+                let longitude: f64 = (2. * s - 1.) * std::f64::consts::PI;
+                let latitude: f64 = (2. * t - 1.) * std::f64::consts::FRAC_PI_2;
+
+                self.forward.scaled(latitude.cos() * longitude.cos())
+                    + self.right.scaled(latitude.cos() * longitude.sin())
+                    + self.down.scaled(latitude.sin())
+            }
+        };
+    }
+
+    // Inverse of get_direction, where a point of the scene is seen on the image
+    pub fn project(&self, point: &Position) -> Option<(f64, f64)> {
+        let offset: Position = *point - self.origin;
+        let x: f64 = offset.dot(&self.right);
+        let y: f64 = offset.dot(&self.down);
+        let z: f64 = offset.dot(&self.forward);
+
+        let (s, t): (f64, f64) = match self.projection {
+            ProjectionMode::Perspective => {
+                if z <= 0. {
+                    return Option::None;
+                }
+                (
+                    (x / z / self.half_width + 1.) / 2.,
+                    (y / z / self.half_height + 1.) / 2.,
+                )
+            }
+            ProjectionMode::Orthographic => {
+                if z <= 0. {
+                    return Option::None;
+                }
+                let half_height: f64 = self.orthographic_height / 2.;
+                (
+                    (x / (half_height * self.aspect_ratio) + 1.) / 2.,
+                    (y / half_height + 1.) / 2.,
+                )
+            }
+            ProjectionMode::Fisheye => {
+                let lateral: f64 = (x * x + y * y).sqrt();
+                if lateral < 1e-9 {
+                    return if z > 0. {
+                        Option::Some((0.5, 0.5))
+                    } else {
+                        Option::None
+                    };
+                }
+                let radius: f64 = lateral.atan2(z) * 2. / self.fisheye_fov;
+                (
+                    (radius * x / lateral / self.aspect_ratio + 1.) / 2.,
+                    (radius * y / lateral + 1.) / 2.,
+                )
+            }
+            ProjectionMode::Equirectangular => {
+                let length: f64 = offset.length();
+                if length < 1e-9 {
+                    return Option::None;
+                }
+                (
+                    (x.atan2(z) / std::f64::consts::PI + 1.) / 2.,
+                    ((y / length).asin() / std::f64::consts::FRAC_PI_2 + 1.) / 2.,
+                )
+            }
+        };
+
+        if s < 0. || s > 1. || t < 0. || t > 1. {
+            return Option::None;
+        }
+
+        return Option::Some((s, t));
+    }
+
+    // Same position, orientation, field of view and lens, the image of a still scene is the same
+    pub fn has_same_pose(&self, other: &Camera) -> bool {
+        return self.origin.dist_squared(&other.origin) < 1e-18
+            && self.forward.dist_squared(&other.forward) < 1e-18
+            && self.right.dist_squared(&other.right) < 1e-18
+            && self.projection == other.projection
+            && self.half_width == other.half_width
+            && self.half_height == other.half_height
+            && self.orthographic_height == other.orthographic_height
+            && self.fisheye_fov == other.fisheye_fov
+            && self.aperture_radius == other.aperture_radius
+            && self.focus_distance == other.focus_distance;
+    }
+
+    // Thin lens: the ray starts on a random point of the aperture and goes through the focus plane
+    pub fn get_ray<R: Rng>(&self, s: f64, t: f64, x_value: i64, y_value: i64, rng: &mut R) -> Ray {
+        let origin: Position = self.origin + self.get_origin_offset(s, t);
+        let direction: Position = self.get_direction(s, t);
+
+        if self.aperture_radius <= 0. {
+            return Ray::new(origin, origin + direction, 1., x_value, y_value);
+        }
+
+        let radius: f64 = self.aperture_radius * rng.gen::<f64>().sqrt();
+        let angle: f64 = rng.gen::<f64>() * 2. * std::f64::consts::PI;
+        let lens_point: Position = origin
+            + self.right.scaled(radius * angle.cos())
+            + self.down.scaled(radius * angle.sin());
+
+        // Focus on a plane for the perspective projection, on a sphere around the camera otherwise
+        let focus_point: Position = match self.projection {
+            ProjectionMode::Perspective => origin + direction.scaled(self.focus_distance),
+            _ => origin + direction.normalized().scaled(self.focus_distance),
+        };
+
+        return Ray::new(lens_point, focus_point, 1., x_value, y_value);
+    }
+}
@@ -0,0 +1,170 @@
+use serde_json::{json, Value};
+
+use crate::position::Position;
+
+#[derive(Clone, Copy)]
+pub enum PathInterpolation {
+    Linear,
+    CatmullRom,
+}
+
+impl PathInterpolation {
+    pub fn to_string(&self) -> &str {
+        return match *self {
+            Self::Linear => "Linear",
+            Self::CatmullRom => "CatmullRom",
+        };
+    }
+
+    pub fn from_string(string: &str) -> Self {
+        return match string {
+            "Linear" => Self::Linear,
+            "CatmullRom" => Self::CatmullRom,
+            _ => Self::CatmullRom,
+        };
+    }
+}
+
+// Pose of the observer at a time of the path, in seconds
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    pub time: f64,
+    pub pos: Position,
+    pub hor_angle: f64,
+    pub ver_angle: f64,
+}
+
+impl Keyframe {
+    fn to_values(&self) -> [f64; 5] {
+        return [
+            self.pos.x,
+            self.pos.y,
+            self.pos.z,
+            self.hor_angle,
+            self.ver_angle,
+        ];
+    }
+
+    fn from_values(time: f64, values: [f64; 5]) -> Keyframe {
+        return Keyframe {
+            time: time,
+            pos: Position {
+                x: values[0],
+                y: values[1],
+                z: values[2],
+            },
+            hor_angle: values[3],
+            ver_angle: values[4],
+        };
+    }
+
+    pub fn to_json(&self) -> Value {
+        return json!({
+            "time": self.time,
+            "pos": {"x": self.pos.x, "y": self.pos.y, "z": self.pos.z},
+            "hor_angle": self.hor_angle,
+            "ver_angle": self.ver_angle,
+        });
+    }
+}
+
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+    pub interpolation: PathInterpolation,
+    pub keyframe_interval: f64,
+}
+
+impl CameraPath {
+    pub fn duration(&self) -> f64 {
+        return match self.keyframes.last() {
+            Some(keyframe) => keyframe.time,
+            None => 0.,
+        };
+    }
+
+    // Added after the last keyframe, keyframe_interval seconds later
+    pub fn add_keyframe(&mut self, pos: Position, hor_angle: f64, ver_angle: f64) {
+        let time: f64 = match self.keyframes.last() {
+            Some(keyframe) => keyframe.time + self.keyframe_interval,
+            None => 0.,
+        };
+
+        self.keyframes.push(Keyframe {
+            time: time,
+            pos: pos,
+            hor_angle: hor_angle,
+            ver_angle: ver_angle,
+        });
+    }
+
+    // Same format as the camera_path section of the parameters file
+    pub fn to_json(&self) -> Value {
+        return json!({
+            "interpolation": self.interpolation.to_string(),
+            "keyframe_interval": self.keyframe_interval,
+            "keyframes": self.keyframes.iter().map(|keyframe: &Keyframe| keyframe.to_json()).collect::<Vec<Value>>(),
+        });
+    }
+
+    // The angle the closest to reference, so that the interpolation takes the short way around
+    fn unwrap_angle(angle: f64, reference: f64) -> f64 {
+        let turn: f64 = 2. * std::f64::consts::PI;
+        return angle - ((angle - reference) / turn).round() * turn;
+    }
+
+    pub fn get_pose(&self, time: f64) -> Option<Keyframe> {
+        let last: usize = match self.keyframes.len() {
+            0 => return Option::None,
+            length => length - 1,
+        };
+
+        if time <= self.keyframes[0].time {
+            return Option::Some(self.keyframes[0]);
+        }
+        if time >= self.keyframes[last].time {
+            return Option::Some(self.keyframes[last]);
+        }
+
+        let index: usize = self
+            .keyframes
+            .iter()
+            .rposition(|keyframe: &Keyframe| keyframe.time <= time)
+            .unwrap_or(0)
+            .min(last - 1);
+
+        // The ends of the path are repeated for the Catmull-Rom tangents
+        let mut points: [[f64; 5]; 4] = [
+            self.keyframes[index.saturating_sub(1)].to_values(),
+            self.keyframes[index].to_values(),
+            self.keyframes[index + 1].to_values(),
+            self.keyframes[(index + 2).min(last)].to_values(),
+        ];
+        for i in 1..4 {
+            points[i][3] = Self::unwrap_angle(points[i][3], points[i - 1][3]);
+        }
+
+        let start: f64 = self.keyframes[index].time;
+        let end: f64 = self.keyframes[index + 1].time;
+        let u: f64 = if end > start {
+            (time - start) / (end - start)
+        } else {
+            1.
+        };
+
+        let mut values: [f64; 5] = [0.; 5];
+        for i in 0..5 {
+            let (p0, p1, p2, p3) = (points[0][i], points[1][i], points[2][i], points[3][i]);
+            values[i] = match self.interpolation {
+                PathInterpolation::Linear => p1 + (p2 - p1) * u,
+                PathInterpolation::CatmullRom => {
+                    0.5 * (2. * p1
+                        + (p2 - p0) * u
+                        + (2. * p0 - 5. * p1 + 4. * p2 - p3) * u * u
+                        + (3. * p1 - p0 - 3. * p2 + p3) * u * u * u)
+                }
+            };
+        }
+
+        return Option::Some(Keyframe::from_values(time, values));
+    }
+}
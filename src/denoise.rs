@@ -0,0 +1,150 @@
+use rayon::prelude::*;
+use sdl2::pixels::Color;
+
+use crate::{frame::Frame, parameters::DenoiserParameters, position::Position};
+
+// B3 spline, the same 1D kernel is used in both directions
+const KERNEL: [f64; 5] = [1. / 16., 1. / 4., 3. / 8., 1. / 4., 1. / 16.];
+// The step doubles at each iteration, past this it is wider than any image
+pub const MAX_ITERATIONS: u64 = 16;
+
+struct GuidePixel {
+    normal: Position,
+    albedo: [f64; 3],
+    depth: f64,
+    has_surface: bool,
+}
+
+fn color_to_array(color: &Color) -> [f64; 3] {
+    return [color.r as f64, color.g as f64, color.b as f64];
+}
+
+fn distance_squared(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    return (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2);
+}
+
+// Edge-avoiding a-trous wavelet filter, guided by the first hit of each pixel
+pub fn denoise(frame: &Frame, parameters: &DenoiserParameters) -> Frame {
+    let width: usize = frame.width as usize;
+    let height: usize = frame.height as usize;
+
+    let mut colors: Vec<[f64; 3]> = vec![[0., 0., 0.]; width * height];
+    let mut guides: Vec<GuidePixel> = Vec::with_capacity(width * height);
+
+    for y in 0..height {
+        for x in 0..width {
+            let key: (i64, i64) = (x as i64, y as i64);
+            match frame.colors.get(&key) {
+                Some(color) => colors[y * width + x] = color_to_array(color),
+                None => {}
+            }
+            guides.push(match frame.surfaces.get(&key) {
+                Some(surface) => GuidePixel {
+                    normal: surface.normal,
+                    albedo: color_to_array(&surface.albedo),
+                    depth: surface.depth,
+                    has_surface: true,
+                },
+                None => GuidePixel {
+                    normal: Position {
+                        x: 0.,
+                        y: 0.,
+                        z: 0.,
+                    },
+                    albedo: [0., 0., 0.],
+                    depth: 0.,
+                    has_surface: false,
+                },
+            });
+        }
+    }
+
+    for iteration in 0..parameters.iterations.min(MAX_ITERATIONS) {
+        let step: i64 = 1 << iteration;
+        // The color edges get less strict as the image gets smoother
+        let sigma_color: f64 = parameters.sigma_color * 2f64.powi(-(iteration as i32));
+
+        colors = (0..width * height)
+            .into_par_iter()
+            .map(|index: usize| {
+                let (x, y) = ((index % width) as i64, (index / width) as i64);
+                let center: &GuidePixel = &guides[index];
+                let center_color: &[f64; 3] = &colors[index];
+
+                let mut sum: [f64; 3] = [0., 0., 0.];
+                let mut total_weight: f64 = 0.;
+
+                for (j, ky) in KERNEL.iter().enumerate() {
+                    for (i, kx) in KERNEL.iter().enumerate() {
+                        let qx: i64 = x + (i as i64 - 2) * step;
+                        let qy: i64 = y + (j as i64 - 2) * step;
+                        if qx < 0 || qy < 0 || qx >= width as i64 || qy >= height as i64 {
+                            continue;
+                        }
+
+                        let q_index: usize = qy as usize * width + qx as usize;
+                        let other: &GuidePixel = &guides[q_index];
+                        if other.has_surface != center.has_surface {
+                            continue;
+                        }
+
+                        let mut weight: f64 = kx
+                            * ky
+                            * (-distance_squared(center_color, &colors[q_index])
+                                / (sigma_color * sigma_color).max(1e-9))
+                            .exp();
+
+                        if center.has_surface {
+                            weight *= center
+                                .normal
+                                .dot(&other.normal)
+                                .max(0.)
+                                .powf(parameters.sigma_normal);
+                            weight *= (-(center.depth - other.depth).abs()
+                                / (parameters.sigma_depth * step as f64).max(1e-9))
+                            .exp();
+                            weight *= (-distance_squared(&center.albedo, &other.albedo)
+                                / (parameters.sigma_albedo * parameters.sigma_albedo).max(1e-9))
+                            .exp();
+                        }
+
+                        for c in 0..3 {
+                            sum[c] += colors[q_index][c] * weight;
+                        }
+                        total_weight += weight;
+                    }
+                }
+
+                return if total_weight > 0. {
+                    [
+                        sum[0] / total_weight,
+                        sum[1] / total_weight,
+                        sum[2] / total_weight,
+                    ]
+                } else {
+                    *center_color
+                };
+            })
+            .collect();
+    }
+
+    let mut new_frame: Frame = Frame::create_empty(frame.width, frame.height);
+    new_frame.surfaces = frame.surfaces.clone();
+    new_frame.samples = frame.samples.clone();
+
+    for y in 0..height {
+        for x in 0..width {
+            let color: &[f64; 3] = &colors[y * width + x];
+            new_frame.colors.insert(
+                (x as i64, y as i64),
+                Color::RGB(
+                    color[0].clamp(0., 255.) as u8,
+                    color[1].clamp(0., 255.) as u8,
+                    color[2].clamp(0., 255.) as u8,
+                ),
+            );
+        }
+    }
+
+    return new_frame;
+}
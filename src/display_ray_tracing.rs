@@ -1,32 +1,166 @@
 use sdl2::{pixels::Color, rect::Point, render::Canvas, video::Window};
 
-use crate::{frame::Frame, observer::Observer, parameters::RayParameters, sphere::Sphere};
+use crate::{
+    denoise::denoise,
+    font::{draw_text, get_text_size},
+    frame::Frame,
+    key_bindings::KeyBindings,
+    observer::Observer,
+    parameters::Parameters,
+    sphere::Sphere,
+    tile::{Tile, TileJob},
+};
 
 pub fn display(
     observer: &mut Observer,
     sphere_vector: &Vec<Sphere>,
-    ray_parameters: &RayParameters,
+    parameters: &Parameters,
     canvas: &mut Canvas<Window>,
-) {
-    canvas.set_draw_color(ray_parameters.background_color);
+) -> Frame {
+    canvas.set_draw_color(parameters.ray_parameters.background_color);
     canvas.clear();
 
     let mut observer_bodies: Vec<Sphere> = vec![];
     observer_bodies.push(observer.body);
 
-    let frame: Frame = observer.get_next_frame(
-        ray_parameters,
+    let mut frame: Frame = observer.get_next_frame(
+        &parameters.ray_parameters,
+        &parameters.observer_parameters,
         &sphere_vector.iter().chain(observer_bodies.iter()).collect(),
+        parameters.physics_parameters.enabled,
     );
 
-    // Displaying the colors
+    if parameters.denoiser_parameters.enabled {
+        frame = denoise(&frame, &parameters.denoiser_parameters);
+    }
+
+    // Displaying the colors of the selected pass
+    draw_frame(&frame, observer, parameters, canvas);
+
+    return frame;
+}
+
+fn draw_frame(
+    frame: &Frame,
+    observer: &Observer,
+    parameters: &Parameters,
+    canvas: &mut Canvas<Window>,
+) {
     frame
-        .colors
+        .get_pass_colors(observer.render_pass, parameters.ray_parameters.bounce_count)
         .into_iter()
         .for_each(|((x, y), color): ((i64, i64), Color)| {
             canvas.set_draw_color(color);
             canvas.draw_point(Point::new(x as i32, y as i32)).unwrap();
         });
+}
+
+// Renders tiles for about batch_time_ms and shows them, returns the frame once all its tiles are done
+pub fn display_tiles(
+    observer: &mut Observer,
+    tile_job: &mut Option<TileJob>,
+    sphere_vector: &Vec<Sphere>,
+    parameters: &Parameters,
+    canvas: &mut Canvas<Window>,
+) -> Option<Frame> {
+    // A new frame is started when the rays changed, or to keep going when the image isn't still
+    let start_new_job: bool = match tile_job {
+        Some(job) => {
+            job.rays_generation != observer.get_rays_generation()
+                || (job.is_finished()
+                    && (observer.accumulation_mode || parameters.physics_parameters.enabled))
+        }
+        None => true,
+    };
+
+    if start_new_job {
+        // All the tiles of the frame see the spheres where they are now
+        let mut spheres: Vec<Sphere> = sphere_vector.clone();
+        spheres.push(observer.body);
+
+        let (width, height) = observer.start_tiled_frame(
+            &parameters.ray_parameters,
+            &parameters.observer_parameters,
+            &spheres.iter().collect(),
+            parameters.physics_parameters.enabled,
+        );
+        *tile_job = Option::Some(TileJob::new(
+            width,
+            height,
+            parameters.tile_parameters.tile_size,
+            parameters.tile_parameters.order,
+            &observer.rays,
+            observer.get_rays_generation(),
+            spheres,
+        ));
+    }
 
-    canvas.present();
+    let job: &mut TileJob = match tile_job {
+        Some(job) if job.is_running() => job,
+        _ => return Option::None,
+    };
+
+    job.render_batch(
+        observer,
+        &parameters.ray_parameters,
+        parameters.tile_parameters.batch_time_ms,
+    )
+    .iter()
+    .for_each(|(_, tile_frame): &(Tile, Frame)| {
+        draw_frame(tile_frame, observer, parameters, canvas);
+    });
+
+    let title: String = if job.is_finished() {
+        format!("CPU Raytracing - done in {:.1}s", job.get_elapsed_seconds())
+    } else {
+        format!(
+            "CPU Raytracing - {:.0}% - ETA {:.1}s",
+            job.get_progress() * 100.,
+            job.get_eta_seconds()
+        )
+    };
+    canvas.window_mut().set_title(&title).unwrap();
+
+    if !job.is_finished() {
+        return Option::None;
+    }
+
+    // Same steps as a full frame once the whole image is there
+    let mut frame: Frame = observer.finish_tiled_frame(
+        job.get_frame(),
+        job.get_elapsed_seconds() * 1000.,
+        &parameters.ray_parameters,
+        &parameters.observer_parameters,
+    );
+
+    if parameters.denoiser_parameters.enabled {
+        frame = denoise(&frame, &parameters.denoiser_parameters);
+    }
+
+    draw_frame(&frame, observer, parameters, canvas);
+
+    return Option::Some(frame);
+}
+
+// The actions and their keys, in the middle of the window
+pub fn draw_help(key_bindings: &KeyBindings, canvas: &mut Canvas<Window>) {
+    let lines: Vec<String> = key_bindings.get_help_lines();
+    let (window_width, window_height) = canvas.output_size().unwrap();
+
+    // As big as it fits
+    let (text_width, text_height) = get_text_size(&lines, 1);
+    let scale: i32 = (window_width as i32 / text_width.max(1))
+        .min(window_height as i32 / text_height.max(1))
+        .clamp(1, 3);
+    let (text_width, text_height) = get_text_size(&lines, scale);
+
+    draw_text(
+        canvas,
+        &lines,
+        ((window_width as i32 - text_width) / 2).max(0),
+        ((window_height as i32 - text_height) / 2).max(0),
+        scale,
+        Color::RGB(255, 255, 255),
+        Color::RGB(0, 0, 0),
+    );
 }
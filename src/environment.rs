@@ -0,0 +1,262 @@
+use rand::Rng;
+use sdl2::pixels::Color;
+
+use crate::{parameters::EnvironmentParameters, position::Position, texture::Texture};
+
+#[derive(Clone, Copy)]
+pub enum EnvironmentType {
+    Color,
+    Sky,
+    Image,
+}
+
+impl EnvironmentType {
+    pub fn to_string(&self) -> &str {
+        return match *self {
+            Self::Color => "Color",
+            Self::Sky => "Sky",
+            Self::Image => "Image",
+        };
+    }
+
+    pub fn from_string(string: &str) -> Self {
+        return match string {
+            "Color" => Self::Color,
+            "Sky" => Self::Sky,
+            "Image" => Self::Image,
+            _ => Self::Color,
+        };
+    }
+}
+
+fn mix(c1: &Color, c2: &Color, ratio: f64) -> Color {
+    let ratio: f64 = ratio.clamp(0., 1.);
+    return Color::RGB(
+        (c1.r as f64 + (c2.r as f64 - c1.r as f64) * ratio) as u8,
+        (c1.g as f64 + (c2.g as f64 - c1.g as f64) * ratio) as u8,
+        (c1.b as f64 + (c2.b as f64 - c1.b as f64) * ratio) as u8,
+    );
+}
+
+// Turns an HDR value into a displayable color and a light factor holding the extra intensity
+fn hdr_to_color(rgb: [f64; 3], light_factor: f64) -> (Color, f64) {
+    let max: f64 = rgb[0].max(rgb[1]).max(rgb[2]);
+    let scale: f64 = if max > 1. { 1. / max } else { 1. };
+
+    return (
+        Color::RGB(
+            (rgb[0] * scale * 255.) as u8,
+            (rgb[1] * scale * 255.) as u8,
+            (rgb[2] * scale * 255.) as u8,
+        ),
+        light_factor * max.max(1.),
+    );
+}
+
+fn luminance(rgb: [f64; 3]) -> f64 {
+    return 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+}
+
+// Equirectangular mapping, up is -z like the rest of the scene
+fn direction_to_uv(direction: &Position, rotation: f64) -> (f64, f64) {
+    let d: Position = direction.normalized();
+    let u: f64 = 0.5 + (d.y.atan2(d.x) + rotation) / (2. * std::f64::consts::PI);
+    let v: f64 = (-d.z).clamp(-1., 1.).acos() / std::f64::consts::PI;
+    return (u.rem_euclid(1.), v);
+}
+
+fn uv_to_direction(u: f64, v: f64, rotation: f64) -> Position {
+    let phi: f64 = (u - 0.5) * 2. * std::f64::consts::PI - rotation;
+    let theta: f64 = v * std::f64::consts::PI;
+    return Position {
+        x: theta.sin() * phi.cos(),
+        y: theta.sin() * phi.sin(),
+        z: -theta.cos(),
+    };
+}
+
+fn get_sky_color(environment: &EnvironmentParameters, direction: &Position) -> (Color, f64) {
+    let d: Position = direction.normalized();
+    let elevation: f64 = -d.z;
+
+    if environment.sun_intensity > 0. && d.angle(&environment.sun_direction) < environment.sun_size
+    {
+        return (environment.sun_color, environment.sun_intensity);
+    }
+
+    return if elevation >= 0. {
+        (
+            mix(
+                &environment.horizon_color,
+                &environment.zenith_color,
+                elevation.sqrt(),
+            ),
+            1.,
+        )
+    } else {
+        (
+            mix(
+                &environment.horizon_color,
+                &environment.ground_color,
+                (-elevation * 4.).sqrt(),
+            ),
+            1.,
+        )
+    };
+}
+
+fn get_image_color(
+    environment: &EnvironmentParameters,
+    texture: &Texture,
+    direction: &Position,
+) -> (Color, f64) {
+    let (u, v) = direction_to_uv(direction, environment.image_rotation);
+    let rgb: [f64; 3] = texture.sample(u, v);
+
+    return hdr_to_color(
+        [
+            rgb[0] * environment.image_exposure,
+            rgb[1] * environment.image_exposure,
+            rgb[2] * environment.image_exposure,
+        ],
+        1.,
+    );
+}
+
+// Color and light factor of the environment seen in the given direction
+pub fn get_color(
+    environment: &EnvironmentParameters,
+    background_color: &Color,
+    background_light_factor: f64,
+    direction: &Position,
+) -> (Color, f64) {
+    let (color, factor) = match environment.type_ {
+        EnvironmentType::Color => (*background_color, 1.),
+        EnvironmentType::Sky => get_sky_color(environment, direction),
+        EnvironmentType::Image => match &environment.image {
+            Some(texture) => get_image_color(environment, texture, direction),
+            None => (*background_color, 1.),
+        },
+    };
+
+    return (color, factor * background_light_factor);
+}
+
+// Cumulative distribution of the image luminance, weighted by the solid angle of each row
+pub fn build_image_cdf(texture: &Texture) -> Vec<f64> {
+    let mut cdf: Vec<f64> = Vec::with_capacity((texture.width() * texture.height()) as usize);
+    let mut total: f64 = 0.;
+
+    for y in 0..texture.height() as i64 {
+        let sin_theta: f64 =
+            ((y as f64 + 0.5) / texture.height() as f64 * std::f64::consts::PI).sin();
+        for x in 0..texture.width() as i64 {
+            total += luminance(texture.get_pixel(x, y)) * sin_theta;
+            cdf.push(total);
+        }
+    }
+
+    return cdf;
+}
+
+fn sample_cone<R: Rng>(axis: &Position, angle: f64, rng: &mut R) -> Position {
+    let axis: Position = axis.normalized();
+    let helper: Position = if axis.x.abs() > 0.9 {
+        Position {
+            x: 0.,
+            y: 1.,
+            z: 0.,
+        }
+    } else {
+        Position {
+            x: 1.,
+            y: 0.,
+            z: 0.,
+        }
+    };
+    let t1: Position = axis.cross(&helper).normalized();
+    let t2: Position = axis.cross(&t1);
+
+    let cos_angle: f64 = 1. - rng.gen::<f64>() * (1. - angle.cos());
+    let sin_angle: f64 = (1. - cos_angle * cos_angle).sqrt();
+    let phi: f64 = rng.gen::<f64>() * 2. * std::f64::consts::PI;
+
+    return axis.scaled(cos_angle)
+        + t1.scaled(sin_angle * phi.cos())
+        + t2.scaled(sin_angle * phi.sin());
+}
+
+// Whether the direction is in the part of the environment that sample_light_direction picks from,
+// a bounce ray reaching it was already counted by the light sample
+pub fn is_sampled_as_light(environment: &EnvironmentParameters, direction: &Position) -> bool {
+    return match environment.type_ {
+        EnvironmentType::Color => false,
+        EnvironmentType::Sky => {
+            environment.sun_intensity > 0.
+                && direction.normalized().angle(&environment.sun_direction) < environment.sun_size
+        }
+        EnvironmentType::Image => {
+            environment.image.is_some()
+                && matches!(environment.image_cdf.last(), Some(total) if *total > 0.)
+        }
+    };
+}
+
+// Picks a direction towards a bright part of the environment, for direct light sampling,
+// with the probability density of that direction per solid angle
+pub fn sample_light_direction<R: Rng>(
+    environment: &EnvironmentParameters,
+    rng: &mut R,
+) -> Option<(Position, f64)> {
+    return match environment.type_ {
+        EnvironmentType::Color => Option::None,
+        EnvironmentType::Sky => {
+            if environment.sun_intensity > 0. {
+                // Uniform in the cone of the sun
+                let solid_angle: f64 =
+                    2. * std::f64::consts::PI * (1. - environment.sun_size.cos());
+                Option::Some((
+                    sample_cone(&environment.sun_direction, environment.sun_size, rng),
+                    1. / solid_angle.max(1e-12),
+                ))
+            } else {
+                Option::None
+            }
+        }
+        EnvironmentType::Image => match (&environment.image, environment.image_cdf.last()) {
+            (Some(texture), Some(total)) if *total > 0. => {
+                let target: f64 = rng.gen::<f64>() * total;
+                let index: usize = environment
+                    .image_cdf
+                    .partition_point(|value: &f64| *value < target)
+                    .min(environment.image_cdf.len() - 1);
+                let x: f64 = (index as u32 % texture.width()) as f64 + rng.gen::<f64>();
+                let y: f64 = (index as u32 / texture.width()) as f64 + rng.gen::<f64>();
+
+                // A texel covers 2 pi^2 sin(theta) / texel count steradians
+                let weight: f64 = environment.image_cdf[index]
+                    - if index > 0 {
+                        environment.image_cdf[index - 1]
+                    } else {
+                        0.
+                    };
+                let texel_count: f64 = (texture.width() * texture.height()) as f64;
+                let theta: f64 = ((index as u32 / texture.width()) as f64 + 0.5)
+                    / texture.height() as f64
+                    * std::f64::consts::PI;
+                let pdf: f64 = weight / total * texel_count
+                    / (2. * std::f64::consts::PI * std::f64::consts::PI * theta.sin()).max(1e-12);
+
+                Option::Some((
+                    uv_to_direction(
+                        x / texture.width() as f64,
+                        y / texture.height() as f64,
+                        environment.image_rotation,
+                    ),
+                    pdf,
+                ))
+            }
+            _ => Option::None,
+        },
+    };
+}
@@ -0,0 +1,69 @@
+#[derive(Clone, Copy)]
+pub enum ReconstructionFilter {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+}
+
+impl ReconstructionFilter {
+    pub fn to_string(&self) -> &str {
+        return match *self {
+            Self::Box => "Box",
+            Self::Tent => "Tent",
+            Self::Gaussian => "Gaussian",
+            Self::Mitchell => "Mitchell",
+        };
+    }
+
+    pub fn from_string(string: &str) -> Self {
+        return match string {
+            "Box" => Self::Box,
+            "Tent" => Self::Tent,
+            "Gaussian" => Self::Gaussian,
+            "Mitchell" => Self::Mitchell,
+            _ => Self::Box,
+        };
+    }
+
+    // Weight of a sample at (dx, dy) pixels from the pixel center, the filters are separable
+    pub fn weight(&self, dx: f64, dy: f64, radius: f64) -> f64 {
+        if dx.abs() > radius || dy.abs() > radius {
+            return 0.;
+        }
+
+        return self.weight_1d(dx, radius) * self.weight_1d(dy, radius);
+    }
+
+    fn weight_1d(&self, d: f64, radius: f64) -> f64 {
+        return match *self {
+            Self::Box => 1.,
+            Self::Tent => (1. - d.abs() / radius).max(0.),
+            Self::Gaussian => {
+                let alpha: f64 = 2.;
+                ((-alpha * d * d).exp() - (-alpha * radius * radius).exp()).max(0.)
+            }
+            Self::Mitchell => mitchell(2. * d / radius),
+        };
+    }
+}
+
+// Mitchell-Netravali with B = C = 1/3, defined on [-2, 2]
+fn mitchell(x: f64) -> f64 {
+    let b: f64 = 1. / 3.;
+    let c: f64 = 1. / 3.;
+    let x: f64 = x.abs();
+
+    return if x < 1. {
+        ((12. - 9. * b - 6. * c) * x * x * x + (-18. + 12. * b + 6. * c) * x * x + (6. - 2. * b))
+            / 6.
+    } else if x < 2. {
+        ((-b - 6. * c) * x * x * x
+            + (6. * b + 30. * c) * x * x
+            + (-12. * b - 48. * c) * x
+            + (8. * b + 24. * c))
+            / 6.
+    } else {
+        0.
+    };
+}
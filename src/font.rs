@@ -0,0 +1,143 @@
+use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window};
+
+const GLYPH_WIDTH: i32 = 5;
+const GLYPH_HEIGHT: i32 = 7;
+// Space between the characters and between the lines
+const CHAR_SPACING: i32 = 1;
+const LINE_SPACING: i32 = 2;
+const PADDING: i32 = 3;
+
+// 5x7 glyphs, one byte per row from the top, the highest of the 5 bits is the left column
+fn get_glyph(character: char) -> [u8; 7] {
+    return match character.to_ascii_uppercase() {
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '"' => [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '\'' => [0x04, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '*' => [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        ';' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08],
+        '<' => [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '>' => [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08],
+        'A' => [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '[' => [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E],
+        '\\' => [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00],
+        ']' => [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '`' => [0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '|' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    };
+}
+
+// Size in window pixels of the box drawn around the lines
+pub fn get_text_size(lines: &Vec<String>, scale: i32) -> (i32, i32) {
+    let columns: i32 = lines
+        .iter()
+        .map(|line: &String| line.chars().count() as i32)
+        .max()
+        .unwrap_or(0);
+    let rows: i32 = lines.len() as i32;
+
+    return (
+        (columns * (GLYPH_WIDTH + CHAR_SPACING) - CHAR_SPACING + 2 * PADDING).max(0) * scale,
+        (rows * (GLYPH_HEIGHT + LINE_SPACING) - LINE_SPACING + 2 * PADDING).max(0) * scale,
+    );
+}
+
+// Lines of text on a box, x and y are in window pixels whatever the scale of the canvas
+pub fn draw_text(
+    canvas: &mut Canvas<Window>,
+    lines: &Vec<String>,
+    x: i32,
+    y: i32,
+    scale: i32,
+    color: Color,
+    background: Color,
+) {
+    let (scale_x, scale_y): (f32, f32) = canvas.scale();
+    canvas.set_scale(1., 1.).unwrap();
+
+    let (width, height): (i32, i32) = get_text_size(lines, scale);
+    canvas.set_draw_color(background);
+    canvas
+        .fill_rect(Rect::new(x, y, width as u32, height as u32))
+        .unwrap();
+
+    let mut pixels: Vec<Rect> = vec![];
+    lines
+        .iter()
+        .enumerate()
+        .for_each(|(row, line): (usize, &String)| {
+            line.chars()
+                .enumerate()
+                .for_each(|(column, character): (usize, char)| {
+                    let left: i32 =
+                        x + (PADDING + column as i32 * (GLYPH_WIDTH + CHAR_SPACING)) * scale;
+                    let top: i32 =
+                        y + (PADDING + row as i32 * (GLYPH_HEIGHT + LINE_SPACING)) * scale;
+
+                    for (glyph_row, bits) in get_glyph(character).iter().enumerate() {
+                        for glyph_column in 0..GLYPH_WIDTH {
+                            if bits & (0x10 >> glyph_column) > 0 {
+                                pixels.push(Rect::new(
+                                    left + glyph_column * scale,
+                                    top + glyph_row as i32 * scale,
+                                    scale as u32,
+                                    scale as u32,
+                                ));
+                            }
+                        }
+                    }
+                });
+        });
+    canvas.set_draw_color(color);
+    canvas.fill_rects(&pixels).unwrap();
+
+    canvas.set_scale(scale_x, scale_y).unwrap();
+}
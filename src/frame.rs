@@ -2,87 +2,334 @@ use std::collections::HashMap;
 
 use sdl2::pixels::Color;
 
-use crate::ray_trace::RayTrace;
+use crate::{
+    parameters::RayParameters,
+    ray_trace::RayTrace,
+    render_pass::{heat_color, RenderPass, SurfaceInfo},
+};
 
 struct PixelAccumulator {
-    pub r: u64,
-    pub g: u64,
-    pub b: u64,
-    pub a: u64,
-    pub count: u64,
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+    pub weight: f64,
 }
 
 impl PixelAccumulator {
+    pub fn new() -> PixelAccumulator {
+        return PixelAccumulator {
+            r: 0.,
+            g: 0.,
+            b: 0.,
+            a: 0.,
+            weight: 0.,
+        };
+    }
+
+    pub fn add(&mut self, color: &Color, weight: f64) {
+        self.r += color.r as f64 * weight;
+        self.g += color.g as f64 * weight;
+        self.b += color.b as f64 * weight;
+        self.a += color.a as f64 * weight;
+        self.weight += weight;
+    }
+
+    pub fn merge(&mut self, other: &PixelAccumulator) {
+        self.r += other.r;
+        self.g += other.g;
+        self.b += other.b;
+        self.a += other.a;
+        self.weight += other.weight;
+    }
+
     pub fn get_color(&self) -> Color {
+        if self.weight <= 0. {
+            return Color::RGBA(0, 0, 0, 255);
+        }
+
         return Color {
-            r: (self.r / self.count) as u8,
-            g: (self.g / self.count) as u8,
-            b: (self.b / self.count) as u8,
-            a: (self.a / self.count) as u8,
+            r: (self.r / self.weight).clamp(0., 255.) as u8,
+            g: (self.g / self.weight).clamp(0., 255.) as u8,
+            b: (self.b / self.weight).clamp(0., 255.) as u8,
+            a: (self.a / self.weight).clamp(0., 255.) as u8,
         };
     }
 }
 
 pub struct Frame {
     pub colors: HashMap<(i64, i64), Color>,
+    pub surfaces: HashMap<(i64, i64), SurfaceInfo>,
+    pub samples: HashMap<(i64, i64), u64>,
+    pub width: i64,
+    pub height: i64,
 }
 
-impl Frame {
-    pub fn create_from_ray_trace(traces: Vec<RayTrace>) -> Frame {
-        let mut colors: HashMap<(i64, i64), Color> = HashMap::new();
+// Weighted sums of the samples splatted on each pixel, a frame traced in parts adds them all up
+// before dividing by the weights, so that the splats cross the borders of the parts
+pub struct FrameAccumulator {
+    pixels: HashMap<(i64, i64), PixelAccumulator>,
+    nearest: HashMap<(i64, i64), Color>,
+    surfaces: HashMap<(i64, i64), SurfaceInfo>,
+    samples: HashMap<(i64, i64), u64>,
+    pub width: i64,
+    pub height: i64,
+}
+
+impl FrameAccumulator {
+    pub fn new(width: i64, height: i64) -> FrameAccumulator {
+        return FrameAccumulator {
+            pixels: HashMap::new(),
+            nearest: HashMap::new(),
+            surfaces: HashMap::new(),
+            samples: HashMap::new(),
+            width: width,
+            height: height,
+        };
+    }
+
+    // Every sample is splatted on the pixels covered by the reconstruction filter
+    pub fn add_traces(&mut self, traces: &Vec<RayTrace>, ray_parameters: &RayParameters) {
+        let radius: f64 = ray_parameters.filter_radius;
+        let reach: i64 = radius.ceil() as i64;
 
         traces.iter().for_each(|trace: &RayTrace| {
-            colors.insert((trace.ray.x_value, trace.ray.y_value), trace.color);
+            for x in (trace.ray.x_value - reach)..=(trace.ray.x_value + reach) {
+                for y in (trace.ray.y_value - reach)..=(trace.ray.y_value + reach) {
+                    if x < 0 || y < 0 || x >= self.width || y >= self.height {
+                        continue;
+                    }
+
+                    let weight: f64 = ray_parameters.reconstruction_filter.weight(
+                        (trace.ray.x_value - x) as f64 + trace.ray.x_offset,
+                        (trace.ray.y_value - y) as f64 + trace.ray.y_offset,
+                        radius,
+                    );
+
+                    if weight != 0. {
+                        self.pixels
+                            .entry((x, y))
+                            .or_insert_with(PixelAccumulator::new)
+                            .add(&trace.color, weight);
+                    }
+                }
+            }
+
+            // The auxiliary passes, and the fallback color, keep the first sample of each pixel
+            let position: (i64, i64) = (trace.ray.x_value, trace.ray.y_value);
+            self.nearest.entry(position).or_insert(trace.color);
+            *self.samples.entry(position).or_insert(0) += 1;
+            match trace.surface {
+                Some(surface) => {
+                    self.surfaces.entry(position).or_insert(surface);
+                }
+                None => {}
+            }
         });
+    }
 
-        return Frame { colors: colors };
+    pub fn merge(&mut self, other: FrameAccumulator) {
+        other.pixels.iter().for_each(
+            |(position, accumulator): (&(i64, i64), &PixelAccumulator)| {
+                self.pixels
+                    .entry(*position)
+                    .or_insert_with(PixelAccumulator::new)
+                    .merge(accumulator);
+            },
+        );
+        other.nearest.into_iter().for_each(|(position, color)| {
+            self.nearest.entry(position).or_insert(color);
+        });
+        other.surfaces.into_iter().for_each(|(position, surface)| {
+            self.surfaces.entry(position).or_insert(surface);
+        });
+        other.samples.into_iter().for_each(|(position, samples)| {
+            *self.samples.entry(position).or_insert(0) += samples;
+        });
     }
 
-    fn create_empty() -> Frame {
+    pub fn get_frame(&self) -> Frame {
+        return self.get_region(0, 0, self.width, self.height);
+    }
+
+    // The pixels of a rectangle of the frame, with the sums added so far
+    pub fn get_region(&self, x: i64, y: i64, width: i64, height: i64) -> Frame {
+        let mut frame: Frame = Frame::create_empty(self.width, self.height);
+
+        for x in x..(x + width).min(self.width) {
+            for y in y..(y + height).min(self.height) {
+                // A pixel can miss all its samples with a narrow filter, or only get the negative
+                // lobes of a wide one, the nearest sample is used then
+                let color: Option<Color> = match self.pixels.get(&(x, y)) {
+                    Some(accumulator) if accumulator.weight > 0. => {
+                        Option::Some(accumulator.get_color())
+                    }
+                    _ => self.nearest.get(&(x, y)).copied(),
+                };
+                match color {
+                    Some(color) => {
+                        frame.colors.insert((x, y), color);
+                    }
+                    None => {}
+                }
+                match self.surfaces.get(&(x, y)) {
+                    Some(surface) => {
+                        frame.surfaces.insert((x, y), *surface);
+                    }
+                    None => {}
+                }
+                match self.samples.get(&(x, y)) {
+                    Some(samples) => {
+                        frame.samples.insert((x, y), *samples);
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        return frame;
+    }
+}
+
+impl Frame {
+    pub fn create_from_ray_trace(
+        traces: Vec<RayTrace>,
+        width: i64,
+        height: i64,
+        ray_parameters: &RayParameters,
+    ) -> Frame {
+        let mut accumulator: FrameAccumulator = FrameAccumulator::new(width, height);
+        accumulator.add_traces(&traces, ray_parameters);
+        return accumulator.get_frame();
+    }
+
+    pub fn create_empty(width: i64, height: i64) -> Frame {
         return Frame {
             colors: HashMap::new(),
+            surfaces: HashMap::new(),
+            samples: HashMap::new(),
+            width: width,
+            height: height,
         };
     }
 
-    pub fn accumulate_frames(frames: &Vec<Frame>) -> Frame {
-        let pixels: &mut HashMap<(i64, i64), PixelAccumulator> = &mut HashMap::new();
-
-        frames.iter().for_each(|frame: &Frame| {
-            frame
-                .colors
-                .iter()
-                .for_each(|(position, color): (&(i64, i64), &Color)| {
-                    if pixels.contains_key(&position) {
-                        let accumulator: &mut PixelAccumulator = pixels.get_mut(position).unwrap();
-
-                        accumulator.r += color.r as u64;
-                        accumulator.g += color.g as u64;
-                        accumulator.b += color.b as u64;
-                        accumulator.a += color.a as u64;
-                        accumulator.count += 1;
-                    } else {
-                        pixels.insert(
-                            *position,
-                            PixelAccumulator {
-                                r: color.r as u64,
-                                g: color.g as u64,
-                                b: color.b as u64,
-                                a: color.a as u64,
-                                count: 1,
-                            },
-                        );
-                    }
-                });
-        });
+    // Nearest neighbour scaling, used to show a frame rendered at a lower resolution
+    pub fn resized(&self, width: i64, height: i64) -> Frame {
+        let mut new_frame: Frame = Frame::create_empty(width, height);
 
-        let mut new_frame: Frame = Frame::create_empty();
+        for x in 0..width {
+            for y in 0..height {
+                let source: (i64, i64) = (x * self.width / width, y * self.height / height);
 
-        pixels.iter().for_each(
-            |(position, accumulator): (&(i64, i64), &PixelAccumulator)| {
-                new_frame.colors.insert(*position, accumulator.get_color());
-            },
-        );
+                match self.colors.get(&source) {
+                    Some(color) => {
+                        new_frame.colors.insert((x, y), *color);
+                    }
+                    None => {}
+                }
+                match self.surfaces.get(&source) {
+                    Some(surface) => {
+                        new_frame.surfaces.insert((x, y), *surface);
+                    }
+                    None => {}
+                }
+                match self.samples.get(&source) {
+                    Some(samples) => {
+                        new_frame.samples.insert((x, y), *samples);
+                    }
+                    None => {}
+                }
+            }
+        }
 
         return new_frame;
     }
+
+    pub fn get_pass_colors(
+        &self,
+        pass: RenderPass,
+        max_bounces: u64,
+    ) -> HashMap<(i64, i64), Color> {
+        match pass {
+            RenderPass::Color => return self.colors.clone(),
+            RenderPass::SampleCount => {
+                let max_samples: u64 = self.samples.values().copied().max().unwrap_or(1);
+
+                return self
+                    .samples
+                    .iter()
+                    .map(|(position, samples): (&(i64, i64), &u64)| {
+                        (
+                            *position,
+                            heat_color(*samples as f64 / max_samples.max(1) as f64),
+                        )
+                    })
+                    .collect();
+            }
+            _ => {}
+        }
+
+        let max_depth: f64 = self
+            .surfaces
+            .values()
+            .map(|surface: &SurfaceInfo| surface.depth)
+            .fold(0., f64::max);
+
+        return self
+            .surfaces
+            .iter()
+            .map(|(position, surface): (&(i64, i64), &SurfaceInfo)| {
+                (*position, pass.get_color(surface, max_depth, max_bounces))
+            })
+            .collect();
+    }
+
+    pub fn save(&self, path: &str) {
+        self.save_colors(&self.colors, path);
+    }
+
+    // Every pass next to the given path, image.png gives image_Color.png, image_Depth.png...
+    pub fn save_passes(&self, path: &str, max_bounces: u64) {
+        let (stem, extension) = match path.rfind('.') {
+            Some(index) => (&path[..index], &path[index..]),
+            None => (path, ".png"),
+        };
+
+        for pass in RenderPass::all() {
+            self.save_colors(
+                &self.get_pass_colors(pass, max_bounces),
+                &format!("{}_{}{}", stem, pass.to_string(), extension),
+            );
+        }
+    }
+
+    // The color pass as an image
+    pub fn get_image(&self) -> image::RgbImage {
+        return self.get_colors_image(&self.colors);
+    }
+
+    fn get_colors_image(&self, colors: &HashMap<(i64, i64), Color>) -> image::RgbImage {
+        let mut image = image::RgbImage::new(self.width as u32, self.height as u32);
+
+        colors
+            .iter()
+            .for_each(|((x, y), color): (&(i64, i64), &Color)| {
+                if *x >= 0 && *y >= 0 && *x < self.width && *y < self.height {
+                    image.put_pixel(
+                        *x as u32,
+                        *y as u32,
+                        image::Rgb([color.r, color.g, color.b]),
+                    );
+                }
+            });
+
+        return image;
+    }
+
+    fn save_colors(&self, colors: &HashMap<(i64, i64), Color>, path: &str) {
+        match self.get_colors_image(colors).save(path) {
+            Ok(_) => println!("Saved {}", path),
+            Err(e) => println!("Unable to save {}: {}", path, e),
+        }
+    }
 }
@@ -0,0 +1,449 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    path::Path,
+    time::Instant,
+};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, DynamicImage,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::{json, Value};
+
+use crate::{
+    denoise::denoise,
+    frame::Frame,
+    observer::Observer,
+    parameters::{get_parameter_file_path, Parameters},
+    physics::PhysicsClock,
+    sphere::Sphere,
+    stereo::StereoMode,
+    tile::TileJob,
+};
+
+// Same as the window display, but the frame is written to a file instead
+pub fn render_to_file(
+    observer: &mut Observer,
+    sphere_vector: &Vec<Sphere>,
+    parameters: &Parameters,
+    path: &str,
+    save_passes: bool,
+) {
+    let mut observer_bodies: Vec<Sphere> = vec![];
+    observer_bodies.push(observer.body);
+
+    let spheres: Vec<&Sphere> = sphere_vector.iter().chain(observer_bodies.iter()).collect();
+
+    let mut frame: Frame = if parameters.tile_parameters.enabled
+        && matches!(parameters.observer_parameters.stereo_mode, StereoMode::Off)
+    {
+        render_tiled(observer, &spheres, parameters)
+    } else {
+        observer.get_next_frame(
+            &parameters.ray_parameters,
+            &parameters.observer_parameters,
+            &spheres,
+            parameters.physics_parameters.enabled,
+        )
+    };
+
+    if parameters.denoiser_parameters.enabled {
+        frame = denoise(&frame, &parameters.denoiser_parameters);
+    }
+
+    if save_passes {
+        frame.save_passes(path, parameters.ray_parameters.bounce_count);
+    } else {
+        frame.save(path);
+    }
+}
+
+// The tiles of one frame, with the progress printed after every batch
+fn render_tiled(observer: &mut Observer, spheres: &Vec<&Sphere>, parameters: &Parameters) -> Frame {
+    let (width, height) = observer.start_tiled_frame(
+        &parameters.ray_parameters,
+        &parameters.observer_parameters,
+        spheres,
+        parameters.physics_parameters.enabled,
+    );
+    let mut job: TileJob = TileJob::new(
+        width,
+        height,
+        parameters.tile_parameters.tile_size,
+        parameters.tile_parameters.order,
+        &observer.rays,
+        observer.get_rays_generation(),
+        spheres.iter().map(|sphere: &&Sphere| **sphere).collect(),
+    );
+
+    while job.is_running() {
+        job.render_batch(
+            observer,
+            &parameters.ray_parameters,
+            parameters.tile_parameters.batch_time_ms,
+        );
+        println!(
+            "{:.0}% - ETA {:.1}s",
+            job.get_progress() * 100.,
+            job.get_eta_seconds()
+        );
+    }
+
+    return observer.finish_tiled_frame(
+        job.get_frame(),
+        job.get_elapsed_seconds() * 1000.,
+        &parameters.ray_parameters,
+        &parameters.observer_parameters,
+    );
+}
+
+const RENDER_USAGE: &str = "Usage: render [--scene <file>] [--width <w>] [--height <h>] \
+[--spp <n> | --time-budget <seconds>] [--output <file>] [--seed <n>] [--passes]";
+const RECORD_USAGE: &str =
+    "Usage: record [--scene <file>] [--width <w>] [--height <h>] [--fps <n>] \
+[--duration <seconds> | --frames <n>] [--time-scale <factor>] [--spp <n>] \
+[--output <frames/frame.png>] [--gif <file>] [--seed <n>] [--camera-path] [--no-physics]";
+
+fn get_argument<'a>(args: &'a Vec<String>, name: &str) -> Option<&'a String> {
+    return match args.iter().position(|arg: &String| arg == name) {
+        Some(index) => args.get(index + 1),
+        None => Option::None,
+    };
+}
+
+fn get_usage(args: &Vec<String>) -> &'static str {
+    return match args.get(1).map(|arg: &String| arg.as_str()) {
+        Some("record") => RECORD_USAGE,
+        _ => RENDER_USAGE,
+    };
+}
+
+// A bad argument ends the command with the usage instead of a panic
+fn exit_with_usage(args: &Vec<String>, message: &str) -> ! {
+    eprintln!("{}", message);
+    eprintln!("{}", get_usage(args));
+    std::process::exit(2);
+}
+
+fn parse_argument<T: std::str::FromStr>(args: &Vec<String>, name: &str) -> Option<T> {
+    return get_argument(args, name).map(|value: &String| match value.parse::<T>() {
+        Ok(parsed) => parsed,
+        Err(_) => exit_with_usage(args, &format!("Invalid value for {}: {}", name, value)),
+    });
+}
+
+// Scene of the commands without window, with the resolution and seed given on the command line
+struct OfflineScene {
+    pub path: String,
+    pub parameters: Parameters,
+    pub sphere_vector: Vec<Sphere>,
+    pub seed: u64,
+    pub parameters_hash: u64,
+    pub width: i64,
+    pub height: i64,
+}
+
+impl OfflineScene {
+    fn from_arguments(args: &Vec<String>) -> OfflineScene {
+        let path: String = get_argument(args, "--scene")
+            .cloned()
+            .unwrap_or(get_parameter_file_path());
+        let seed: u64 = parse_argument(args, "--seed").unwrap_or(rand::thread_rng().gen());
+
+        let scene_text: String = fs::read_to_string(&path).expect("Unable to read scene file");
+        let mut hasher: DefaultHasher = DefaultHasher::new();
+        scene_text.hash(&mut hasher);
+
+        let mut parameters: Parameters = Parameters::get_from_json_file(&path, Option::None);
+
+        // The resolution replaces the one of the scene, still centered on the view
+        let width: i64 = parse_argument(args, "--width").unwrap_or(
+            parameters.ray_parameters.max_hor_value - parameters.ray_parameters.min_hor_value,
+        );
+        let height: i64 = parse_argument(args, "--height").unwrap_or(
+            parameters.ray_parameters.max_ver_value - parameters.ray_parameters.min_ver_value,
+        );
+        parameters.ray_parameters.min_hor_value = -width / 2;
+        parameters.ray_parameters.max_hor_value = width - width / 2;
+        parameters.ray_parameters.min_ver_value = -height / 2;
+        parameters.ray_parameters.max_ver_value = height - height / 2;
+
+        // The seed makes the scene reproducible, the observer also seeds its samples with it
+        let mut rng: StdRng = StdRng::seed_from_u64(seed);
+        let mut sphere_vector: Vec<Sphere> = vec![];
+        Sphere::fill_vector_multiple_parameters(
+            &mut sphere_vector,
+            &parameters.sphere_parameters,
+            &parameters.physics_parameters,
+            &mut rng,
+        );
+
+        return OfflineScene {
+            path: path,
+            parameters: parameters,
+            sphere_vector: sphere_vector,
+            seed: seed,
+            parameters_hash: hasher.finish(),
+            width: width,
+            height: height,
+        };
+    }
+}
+
+// Final render: frames are accumulated until the samples or the time budget are reached
+// render [--scene <file>] [--width <w>] [--height <h>] [--spp <n> | --time-budget <seconds>]
+//        [--output <file>] [--seed <n>] [--passes]
+pub fn render_command(args: &Vec<String>) {
+    let output: String = get_argument(args, "--output")
+        .cloned()
+        .unwrap_or("render.png".to_string());
+    let time_budget: Option<f64> = parse_argument(args, "--time-budget");
+
+    let scene: OfflineScene = OfflineScene::from_arguments(args);
+    let parameters: &Parameters = &scene.parameters;
+
+    let samples_per_frame: u64 = parameters.ray_parameters.samples_per_pixel.max(1);
+    let samples_per_pixel: u64 = parse_argument(args, "--spp").unwrap_or(samples_per_frame);
+    let frame_count: u64 = (samples_per_pixel + samples_per_frame - 1) / samples_per_frame;
+
+    let mut observer: Observer = Observer::default(&parameters);
+    observer.seed = Option::Some(scene.seed);
+    observer.switch_accumulation_mode();
+    let mut observer_bodies: Vec<Sphere> = vec![];
+    observer_bodies.push(observer.body);
+    let spheres: Vec<&Sphere> = scene
+        .sphere_vector
+        .iter()
+        .chain(observer_bodies.iter())
+        .collect();
+
+    let start: Instant = Instant::now();
+    let mut frames_rendered: u64 = 0;
+    let mut frame: Frame;
+
+    loop {
+        frame = observer.get_next_frame(
+            &parameters.ray_parameters,
+            &parameters.observer_parameters,
+            &spheres,
+            false,
+        );
+        frames_rendered += 1;
+
+        let elapsed: f64 = start.elapsed().as_secs_f64();
+        let done: bool = match time_budget {
+            Some(budget) => elapsed >= budget,
+            None => frames_rendered >= frame_count,
+        };
+        let progress: f64 = match time_budget {
+            Some(budget) => elapsed / budget,
+            None => frames_rendered as f64 / frame_count as f64,
+        };
+        println!(
+            "{} spp - {:.0}% - ETA {:.1}s",
+            frames_rendered * samples_per_frame,
+            progress.min(1.) * 100.,
+            elapsed * (1. - progress).max(0.) / progress
+        );
+
+        if done {
+            break;
+        }
+    }
+
+    if parameters.denoiser_parameters.enabled {
+        frame = denoise(&frame, &parameters.denoiser_parameters);
+    }
+
+    if args.contains(&"--passes".to_string()) {
+        frame.save_passes(&output, parameters.ray_parameters.bounce_count);
+    } else {
+        frame.save(&output);
+    }
+
+    let stats: Value = json!({
+        "scene": scene.path,
+        "output": output,
+        "width": scene.width,
+        "height": scene.height,
+        "samples_per_pixel": frames_rendered * samples_per_frame,
+        "frames": frames_rendered,
+        "rays_traced": observer.get_rays_traced(),
+        "render_time_seconds": start.elapsed().as_secs_f64(),
+        "seed": scene.seed,
+        "parameters_hash": format!("{:016x}", scene.parameters_hash),
+    });
+
+    let stats_path: String = match output.rfind('.') {
+        Some(index) => format!("{}.json", &output[..index]),
+        None => format!("{}.json", output),
+    };
+    match fs::write(&stats_path, serde_json::to_string_pretty(&stats).unwrap()) {
+        Ok(_) => println!("Saved {}", stats_path),
+        Err(e) => println!("Unable to save {}: {}", stats_path, e),
+    }
+}
+
+// frames/frame.png gives frames/frame_0000.png, frames/frame_0001.png...
+fn get_numbered_path(path: &str, index: u64) -> String {
+    return match path.rfind('.') {
+        Some(dot) => format!("{}_{:04}{}", &path[..dot], index, &path[dot..]),
+        None => format!("{}_{:04}.png", path, index),
+    };
+}
+
+fn create_parent_directory(path: &str) {
+    match Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => {
+            fs::create_dir_all(parent).expect("Unable to create the output directory")
+        }
+        _ => {}
+    }
+}
+
+// Physics recording: the simulation advances by 1 / fps seconds, times the time scale, between two frames
+// record [--scene <file>] [--width <w>] [--height <h>] [--fps <n>] [--duration <seconds> | --frames <n>]
+//        [--time-scale <factor>] [--spp <n>] [--output <frames/frame.png>] [--gif <file>] [--seed <n>]
+//        [--camera-path] [--no-physics]
+pub fn record_command(args: &Vec<String>) {
+    let mut scene: OfflineScene = OfflineScene::from_arguments(args);
+
+    let fps: f64 = parse_argument(args, "--fps").unwrap_or(30.);
+    // The GIF delay takes the rate in thousandths of a frame per second, a lower rate would give 0
+    if !(fps >= 0.001 && fps.is_finite()) {
+        exit_with_usage(
+            args,
+            &format!(
+                "Invalid value for --fps: {}, it must be at least 0.001",
+                fps
+            ),
+        );
+    }
+    // The camera follows the path of the scene file, and the recording lasts as long as the path
+    let follow_path: bool = args.contains(&"--camera-path".to_string());
+    if follow_path && scene.parameters.camera_path.keyframes.is_empty() {
+        exit_with_usage(
+            args,
+            "--camera-path needs keyframes in the camera_path section of the scene",
+        );
+    }
+    let default_duration: f64 = if follow_path {
+        scene.parameters.camera_path.duration()
+    } else {
+        3.
+    };
+    let frame_count: u64 = parse_argument(args, "--frames").unwrap_or(
+        (parse_argument::<f64>(args, "--duration").unwrap_or(default_duration) * fps).round()
+            as u64
+            + if follow_path { 1 } else { 0 },
+    );
+    let physics: bool = !args.contains(&"--no-physics".to_string());
+    match parse_argument(args, "--time-scale") {
+        Some(time_scale) => scene.parameters.physics_parameters.time_scale = time_scale,
+        None => {}
+    }
+    match parse_argument(args, "--spp") {
+        Some(samples_per_pixel) => {
+            scene.parameters.ray_parameters.samples_per_pixel = samples_per_pixel
+        }
+        None => {}
+    }
+
+    // Without any output given, the recording is a GIF
+    let sequence_path: Option<String> = get_argument(args, "--output").cloned();
+    let gif_path: Option<String> = match (get_argument(args, "--gif"), &sequence_path) {
+        (Some(path), _) => Option::Some(path.clone()),
+        (None, Some(_)) => Option::None,
+        (None, None) => Option::Some("recording.gif".to_string()),
+    };
+
+    let mut gif_encoder: Option<GifEncoder<File>> = gif_path.as_ref().map(|path: &String| {
+        create_parent_directory(path);
+        let mut encoder: GifEncoder<File> =
+            GifEncoder::new(File::create(path).expect("Unable to create the GIF file"));
+        encoder.set_repeat(Repeat::Infinite).unwrap();
+        encoder
+    });
+    match &sequence_path {
+        Some(path) => create_parent_directory(path),
+        None => {}
+    }
+
+    let parameters: &Parameters = &scene.parameters;
+    let mut observer: Observer = Observer::default(parameters);
+    observer.seed = Option::Some(scene.seed);
+    observer.frame_interval = 1. / fps;
+    let mut observer_bodies: Vec<Sphere> = vec![];
+    observer_bodies.push(observer.body);
+    let mut physics_clock: PhysicsClock = PhysicsClock::new();
+    let start: Instant = Instant::now();
+
+    for index in 0..frame_count {
+        if follow_path {
+            match parameters.camera_path.get_pose(index as f64 / fps) {
+                Some(pose) => observer.set_pose(
+                    &pose,
+                    &parameters.observer_parameters,
+                    &parameters.ray_parameters,
+                ),
+                None => {}
+            }
+        }
+
+        let mut frame: Frame = observer.get_next_frame(
+            &parameters.ray_parameters,
+            &parameters.observer_parameters,
+            &scene
+                .sphere_vector
+                .iter()
+                .chain(observer_bodies.iter())
+                .collect(),
+            true,
+        );
+
+        if parameters.denoiser_parameters.enabled {
+            frame = denoise(&frame, &parameters.denoiser_parameters);
+        }
+
+        match &sequence_path {
+            Some(path) => frame.save(&get_numbered_path(path, index)),
+            None => {}
+        }
+        match &mut gif_encoder {
+            Some(encoder) => encoder
+                .encode_frame(image::Frame::from_parts(
+                    DynamicImage::ImageRgb8(frame.get_image()).to_rgba8(),
+                    0,
+                    0,
+                    Delay::from_numer_denom_ms(1000000, (fps * 1000.) as u32),
+                ))
+                .expect("Unable to write the GIF frame"),
+            None => {}
+        }
+
+        let elapsed: f64 = start.elapsed().as_secs_f64();
+        let progress: f64 = (index + 1) as f64 / frame_count as f64;
+        println!(
+            "Frame {}/{} - ETA {:.1}s",
+            index + 1,
+            frame_count,
+            elapsed * (1. - progress) / progress
+        );
+
+        if physics {
+            physics_clock.advance(
+                1. / fps,
+                &mut scene.sphere_vector,
+                &parameters.physics_parameters,
+            );
+        }
+    }
+
+    match &gif_path {
+        Some(path) => println!("Saved {}", path),
+        None => {}
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use sdl2::pixels::Color;
+
+use crate::{camera::Camera, frame::Frame, render_pass::SurfaceInfo};
+
+// Running average of a pixel, kept in floats so that long accumulations don't drift with rounding
+#[derive(Clone, Copy)]
+struct HistoryPixel {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub luminance_square: f64,
+    pub frames: f64,
+    pub samples: u64,
+}
+
+impl HistoryPixel {
+    fn luminance(r: f64, g: f64, b: f64) -> f64 {
+        return 0.299 * r + 0.587 * g + 0.114 * b;
+    }
+
+    // Standard error of the mean luminance over the frames, relative to that luminance
+    fn get_relative_error(&self, max_frames: u64) -> f64 {
+        let mean: f64 = Self::luminance(self.r, self.g, self.b);
+        let variance: f64 = (self.luminance_square - mean * mean).max(0.);
+        let frames: f64 = if max_frames > 0 {
+            self.frames.min(max_frames as f64)
+        } else {
+            self.frames
+        };
+
+        return (variance / frames).sqrt() / (mean + 1.);
+    }
+}
+
+// The accumulated frames, and where they were seen from
+pub struct History {
+    pixels: HashMap<(i64, i64), HistoryPixel>,
+    surfaces: HashMap<(i64, i64), SurfaceInfo>,
+    camera: Option<Camera>,
+    width: i64,
+    height: i64,
+}
+
+impl History {
+    pub fn new() -> History {
+        return History {
+            pixels: HashMap::new(),
+            surfaces: HashMap::new(),
+            camera: Option::None,
+            width: 0,
+            height: 0,
+        };
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.clear();
+        self.surfaces.clear();
+        self.camera = Option::None;
+    }
+
+    pub fn is_seen_from(&self, camera: &Camera) -> bool {
+        return match &self.camera {
+            Some(previous) => previous.has_same_pose(camera),
+            None => false,
+        };
+    }
+
+    // Samples to trace for a pixel, more where the accumulated result is still noisy, none once it's clean
+    pub fn get_adaptive_sample_count(
+        &self,
+        position: &(i64, i64),
+        samples_per_pixel: u64,
+        error_threshold: f64,
+        min_samples: u64,
+        max_samples_per_frame: u64,
+        max_frames: u64,
+    ) -> u64 {
+        let pixel: &HistoryPixel = match self.pixels.get(position) {
+            Some(pixel) => pixel,
+            None => return samples_per_pixel,
+        };
+
+        if pixel.samples < min_samples {
+            return samples_per_pixel;
+        }
+
+        let error: f64 = pixel.get_relative_error(max_frames);
+        if error <= error_threshold {
+            return 0;
+        }
+
+        return ((samples_per_pixel as f64 * error / error_threshold).ceil() as u64).clamp(
+            samples_per_pixel,
+            max_samples_per_frame.max(samples_per_pixel),
+        );
+    }
+
+    // The history pixel that saw the same point as the new first hit, if it was not hidden
+    fn find_reprojected(
+        &self,
+        surface: Option<&SurfaceInfo>,
+        depth_tolerance: f64,
+    ) -> Option<HistoryPixel> {
+        let surface: &SurfaceInfo = surface?;
+        let (s, t) = self.camera?.project(&surface.position)?;
+
+        let x: i64 = ((s * self.width as f64) as i64).min(self.width - 1);
+        let y: i64 = ((t * self.height as f64) as i64).min(self.height - 1);
+
+        // Disoccluded: something else was in front of this point in the previous pose
+        let previous: &SurfaceInfo = self.surfaces.get(&(x, y))?;
+        if previous.object_id != surface.object_id
+            || previous.position.dist(&surface.position) > depth_tolerance * surface.depth
+        {
+            return Option::None;
+        }
+
+        return self.pixels.get(&(x, y)).copied();
+    }
+
+    // Blends a new frame into the history and returns the result, the frame must be seen by camera
+    pub fn accumulate(
+        &mut self,
+        frame: &Frame,
+        camera: &Camera,
+        reproject: bool,
+        max_frames: u64,
+        max_reprojected_frames: u64,
+        depth_tolerance: f64,
+    ) -> Frame {
+        let moved: bool = match &self.camera {
+            Some(previous) => !previous.has_same_pose(camera),
+            None => false,
+        };
+
+        if frame.width != self.width || frame.height != self.height || (moved && !reproject) {
+            self.clear();
+        }
+
+        let mut pixels: HashMap<(i64, i64), HistoryPixel> = HashMap::new();
+        let mut new_frame: Frame = Frame::create_empty(frame.width, frame.height);
+        new_frame.surfaces = frame.surfaces.clone();
+
+        frame
+            .colors
+            .iter()
+            .for_each(|(position, color): (&(i64, i64), &Color)| {
+                // A pixel skipped by the adaptive sampling can still get the splats of its neighbours,
+                // it is not a new sample of that pixel and its history is kept below
+                if !moved
+                    && !frame.samples.contains_key(position)
+                    && self.pixels.contains_key(position)
+                {
+                    return;
+                }
+
+                let samples: u64 = *frame.samples.get(position).unwrap_or(&1);
+                let previous: Option<HistoryPixel> = if moved {
+                    // The first hit is the same, what it reflects may not be: a long history would stay stale
+                    self.find_reprojected(frame.surfaces.get(position), depth_tolerance)
+                        .map(|mut pixel: HistoryPixel| {
+                            pixel.samples =
+                                pixel.samples.min(samples * max_reprojected_frames.max(1));
+                            pixel
+                        })
+                } else {
+                    self.pixels.get(position).copied()
+                };
+                let (r, g, b): (f64, f64, f64) = (color.r as f64, color.g as f64, color.b as f64);
+                let luminance: f64 = HistoryPixel::luminance(r, g, b);
+
+                let pixel: HistoryPixel = match previous {
+                    Some(previous) => {
+                        // Frames are weighted by their samples, the oldest ones fade out past max_frames
+                        let mut blend: f64 = samples as f64 / (previous.samples + samples) as f64;
+                        if max_frames > 0 {
+                            blend = blend.max(1. / max_frames as f64);
+                        }
+                        HistoryPixel {
+                            r: previous.r + (r - previous.r) * blend,
+                            g: previous.g + (g - previous.g) * blend,
+                            b: previous.b + (b - previous.b) * blend,
+                            luminance_square: previous.luminance_square
+                                + (luminance * luminance - previous.luminance_square) * blend,
+                            frames: previous.frames + 1.,
+                            samples: previous.samples + samples,
+                        }
+                    }
+                    None => HistoryPixel {
+                        r: r,
+                        g: g,
+                        b: b,
+                        luminance_square: luminance * luminance,
+                        frames: 1.,
+                        samples: samples,
+                    },
+                };
+
+                pixels.insert(*position, pixel);
+            });
+
+        // Pixels left out by the adaptive sampling keep their history
+        if !moved {
+            self.pixels
+                .iter()
+                .for_each(|(position, pixel): (&(i64, i64), &HistoryPixel)| {
+                    if !pixels.contains_key(position) {
+                        pixels.insert(*position, *pixel);
+                        match self.surfaces.get(position) {
+                            Some(surface) => {
+                                new_frame.surfaces.insert(*position, *surface);
+                            }
+                            None => {}
+                        }
+                    }
+                });
+        }
+
+        pixels
+            .iter()
+            .for_each(|(position, pixel): (&(i64, i64), &HistoryPixel)| {
+                new_frame.colors.insert(
+                    *position,
+                    Color::RGB(
+                        pixel.r.round().clamp(0., 255.) as u8,
+                        pixel.g.round().clamp(0., 255.) as u8,
+                        pixel.b.round().clamp(0., 255.) as u8,
+                    ),
+                );
+                new_frame.samples.insert(*position, pixel.samples);
+            });
+
+        self.pixels = pixels;
+        self.surfaces = new_frame.surfaces.clone();
+        self.camera = Option::Some(*camera);
+        self.width = frame.width;
+        self.height = frame.height;
+
+        return new_frame;
+    }
+}
@@ -0,0 +1,124 @@
+use std::time::Instant;
+
+use sdl2::{pixels::Color, render::Canvas, video::Window};
+
+use crate::{
+    font::draw_text, frame::Frame, observer::Observer, parameters::Parameters, sphere::Sphere,
+};
+
+// Frame rate and rays traced, averaged over update_interval_ms so that the numbers can be read
+pub struct Hud {
+    start: Instant,
+    frames: u64,
+    rays_at_start: u64,
+    fps: f64,
+    frame_time_ms: f64,
+    rays_per_second: f64,
+}
+
+impl Hud {
+    pub fn new() -> Hud {
+        return Hud {
+            start: Instant::now(),
+            frames: 0,
+            rays_at_start: 0,
+            fps: 0.,
+            frame_time_ms: 0.,
+            rays_per_second: 0.,
+        };
+    }
+
+    // Called once per displayed frame, rays_traced is the total count of the observer
+    pub fn update(&mut self, rays_traced: u64, update_interval_ms: f64) {
+        self.frames += 1;
+
+        let elapsed: f64 = self.start.elapsed().as_secs_f64();
+        if elapsed * 1000. < update_interval_ms {
+            return;
+        }
+
+        self.fps = self.frames as f64 / elapsed;
+        self.frame_time_ms = elapsed * 1000. / self.frames as f64;
+        self.rays_per_second = rays_traced.saturating_sub(self.rays_at_start) as f64 / elapsed;
+
+        self.start = Instant::now();
+        self.frames = 0;
+        self.rays_at_start = rays_traced;
+    }
+
+    // 1234567 -> 1.23M
+    fn format_count(count: f64) -> String {
+        return match count {
+            count if count >= 1e9 => format!("{:.2}G", count / 1e9),
+            count if count >= 1e6 => format!("{:.2}M", count / 1e6),
+            count if count >= 1e3 => format!("{:.1}K", count / 1e3),
+            count => format!("{:.0}", count),
+        };
+    }
+
+    fn on_off(value: bool) -> &'static str {
+        return if value { "on" } else { "off" };
+    }
+
+    pub fn get_lines(
+        &self,
+        observer: &Observer,
+        last_frame: &Option<Frame>,
+        sphere_vector: &Vec<Sphere>,
+        parameters: &Parameters,
+    ) -> Vec<String> {
+        // Accumulated samples per pixel of the last displayed frame
+        let samples: f64 = match last_frame {
+            Some(frame) if !frame.samples.is_empty() => {
+                frame.samples.values().sum::<u64>() as f64 / frame.samples.len() as f64
+            }
+            _ => 0.,
+        };
+
+        return vec![
+            format!("FPS: {:.1}", self.fps),
+            format!("Frame: {:.1} ms", self.frame_time_ms),
+            format!("Rays/s: {}", Self::format_count(self.rays_per_second)),
+            format!(
+                "Pos: {:.2} {:.2} {:.2}",
+                observer.body.pos.x, observer.body.pos.y, observer.body.pos.z
+            ),
+            format!(
+                "Angles: {:.1} {:.1} {:.1}",
+                observer.hor_angle.to_degrees(),
+                observer.ver_angle.to_degrees(),
+                observer.roll_angle.to_degrees()
+            ),
+            format!(
+                "Samples: {:.1}, accumulation {}",
+                samples,
+                Self::on_off(observer.accumulation_mode)
+            ),
+            format!(
+                "Physics: {}",
+                Self::on_off(parameters.physics_parameters.enabled)
+            ),
+            format!("Spheres: {}", sphere_vector.len()),
+        ];
+    }
+
+    // In the top left corner of the window
+    pub fn draw(
+        &self,
+        observer: &Observer,
+        last_frame: &Option<Frame>,
+        sphere_vector: &Vec<Sphere>,
+        parameters: &Parameters,
+        canvas: &mut Canvas<Window>,
+    ) {
+        draw_text(
+            canvas,
+            &self.get_lines(observer, last_frame, sphere_vector, parameters),
+            0,
+            0,
+            parameters.hud_parameters.text_scale.max(1) as i32,
+            Color::RGB(255, 255, 255),
+            Color::RGB(0, 0, 0),
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use sdl2::keyboard::KeyboardState;
+
+use crate::key_bindings::{Action, KeyBindings};
+
+// Keys held down at this frame, each axis is -1, 0 or 1
+pub struct InputState {
+    pub forward: f64,
+    pub right: f64,
+    pub up: f64,
+    pub turn_right: f64,
+    pub turn_down: f64,
+    pub roll_right: f64,
+    pub slow: bool,
+}
+
+impl InputState {
+    pub fn from_keyboard(keyboard: &KeyboardState, key_bindings: &KeyBindings) -> InputState {
+        let axis = |positive: Action, negative: Action| -> f64 {
+            let mut value: f64 = 0.;
+            if key_bindings.is_held(positive, keyboard) {
+                value += 1.;
+            }
+            if key_bindings.is_held(negative, keyboard) {
+                value -= 1.;
+            }
+            return value;
+        };
+
+        return InputState {
+            forward: axis(Action::MoveForward, Action::MoveBackward),
+            right: axis(Action::MoveRight, Action::MoveLeft),
+            up: axis(Action::MoveUp, Action::MoveDown),
+            turn_right: axis(Action::TurnRight, Action::TurnLeft),
+            turn_down: axis(Action::LookDown, Action::LookUp),
+            roll_right: axis(Action::RollRight, Action::RollLeft),
+            slow: key_bindings.is_held(Action::SlowMode, keyboard),
+        };
+    }
+}
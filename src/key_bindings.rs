@@ -0,0 +1,450 @@
+use std::fmt;
+
+use sdl2::keyboard::{KeyboardState, Mod, Scancode};
+use serde_json::Value;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    TurnLeft,
+    TurnRight,
+    LookUp,
+    LookDown,
+    RollLeft,
+    RollRight,
+    SlowMode,
+    Quit,
+    ResetPosition,
+    SwitchAccumulation,
+    FocusFarther,
+    FocusCloser,
+    SwitchVisibility,
+    SwitchPhysics,
+    SwitchDenoiser,
+    ReloadParameters,
+    RegenerateSpheres,
+    Screenshot,
+    NextRenderPass,
+    SwitchMovementMode,
+    PlayCameraPath,
+    AddKeyframe,
+    ReleaseMouse,
+    SwitchHud,
+    Help,
+}
+
+impl Action {
+    // In the order of the help
+    pub const ALL: [Action; 31] = [
+        Self::MoveForward,
+        Self::MoveBackward,
+        Self::MoveLeft,
+        Self::MoveRight,
+        Self::MoveUp,
+        Self::MoveDown,
+        Self::TurnLeft,
+        Self::TurnRight,
+        Self::LookUp,
+        Self::LookDown,
+        Self::RollLeft,
+        Self::RollRight,
+        Self::SlowMode,
+        Self::SwitchMovementMode,
+        Self::ReleaseMouse,
+        Self::ResetPosition,
+        Self::FocusFarther,
+        Self::FocusCloser,
+        Self::SwitchAccumulation,
+        Self::SwitchDenoiser,
+        Self::NextRenderPass,
+        Self::SwitchVisibility,
+        Self::SwitchPhysics,
+        Self::RegenerateSpheres,
+        Self::ReloadParameters,
+        Self::PlayCameraPath,
+        Self::AddKeyframe,
+        Self::Screenshot,
+        Self::SwitchHud,
+        Self::Help,
+        Self::Quit,
+    ];
+
+    pub fn to_string(&self) -> &str {
+        return match *self {
+            Self::MoveForward => "MoveForward",
+            Self::MoveBackward => "MoveBackward",
+            Self::MoveLeft => "MoveLeft",
+            Self::MoveRight => "MoveRight",
+            Self::MoveUp => "MoveUp",
+            Self::MoveDown => "MoveDown",
+            Self::TurnLeft => "TurnLeft",
+            Self::TurnRight => "TurnRight",
+            Self::LookUp => "LookUp",
+            Self::LookDown => "LookDown",
+            Self::RollLeft => "RollLeft",
+            Self::RollRight => "RollRight",
+            Self::SlowMode => "SlowMode",
+            Self::Quit => "Quit",
+            Self::ResetPosition => "ResetPosition",
+            Self::SwitchAccumulation => "SwitchAccumulation",
+            Self::FocusFarther => "FocusFarther",
+            Self::FocusCloser => "FocusCloser",
+            Self::SwitchVisibility => "SwitchVisibility",
+            Self::SwitchPhysics => "SwitchPhysics",
+            Self::SwitchDenoiser => "SwitchDenoiser",
+            Self::ReloadParameters => "ReloadParameters",
+            Self::RegenerateSpheres => "RegenerateSpheres",
+            Self::Screenshot => "Screenshot",
+            Self::NextRenderPass => "NextRenderPass",
+            Self::SwitchMovementMode => "SwitchMovementMode",
+            Self::PlayCameraPath => "PlayCameraPath",
+            Self::AddKeyframe => "AddKeyframe",
+            Self::ReleaseMouse => "ReleaseMouse",
+            Self::SwitchHud => "SwitchHud",
+            Self::Help => "Help",
+        };
+    }
+
+    pub fn get_description(&self) -> &str {
+        return match *self {
+            Self::MoveForward => "Move forward",
+            Self::MoveBackward => "Move backward",
+            Self::MoveLeft => "Move left",
+            Self::MoveRight => "Move right",
+            Self::MoveUp => "Move up, jump when walking",
+            Self::MoveDown => "Move down",
+            Self::TurnLeft => "Turn left",
+            Self::TurnRight => "Turn right",
+            Self::LookUp => "Look up",
+            Self::LookDown => "Look down",
+            Self::RollLeft => "Roll left",
+            Self::RollRight => "Roll right",
+            Self::SlowMode => "Slow mode, held",
+            Self::Quit => "Cancel the render or quit",
+            Self::ResetPosition => "Reset the position",
+            Self::SwitchAccumulation => "Accumulation on/off",
+            Self::FocusFarther => "Focus farther",
+            Self::FocusCloser => "Focus closer",
+            Self::SwitchVisibility => "Show/hide the observer",
+            Self::SwitchPhysics => "Physics on/off",
+            Self::SwitchDenoiser => "Denoiser on/off",
+            Self::ReloadParameters => "Reload the parameters",
+            Self::RegenerateSpheres => "Generate new spheres",
+            Self::Screenshot => "Screenshot",
+            Self::NextRenderPass => "Next render pass",
+            Self::SwitchMovementMode => "Fly/walk",
+            Self::PlayCameraPath => "Play/stop the camera path",
+            Self::AddKeyframe => "Add a keyframe",
+            Self::ReleaseMouse => "Release/capture the mouse",
+            Self::SwitchHud => "Show/hide the stats",
+            Self::Help => "Show/hide this help",
+        };
+    }
+
+    // Steps that can follow the key repeat, the other actions happen once per press
+    pub fn is_repeatable(&self) -> bool {
+        return matches!(*self, Self::FocusFarther | Self::FocusCloser);
+    }
+}
+
+// Physical keys, named after the US layout, so that the movement keys stay in place on any layout
+fn get_named_scancodes() -> Vec<(String, Scancode)> {
+    let mut names: Vec<(String, Scancode)> = vec![];
+
+    for (index, letter) in ('A'..='Z').enumerate() {
+        names.push((
+            letter.to_string(),
+            Scancode::from_i32(Scancode::A as i32 + index as i32).unwrap(),
+        ));
+    }
+    for digit in 1..=9 {
+        names.push((
+            digit.to_string(),
+            Scancode::from_i32(Scancode::Num1 as i32 + digit - 1).unwrap(),
+        ));
+    }
+    names.push(("0".to_string(), Scancode::Num0));
+    for number in 1..=12 {
+        names.push((
+            format!("F{}", number),
+            Scancode::from_i32(Scancode::F1 as i32 + number - 1).unwrap(),
+        ));
+    }
+
+    let others: [(&str, Scancode); 29] = [
+        ("Up", Scancode::Up),
+        ("Down", Scancode::Down),
+        ("Left", Scancode::Left),
+        ("Right", Scancode::Right),
+        ("Space", Scancode::Space),
+        ("Return", Scancode::Return),
+        ("Escape", Scancode::Escape),
+        ("Tab", Scancode::Tab),
+        ("Backspace", Scancode::Backspace),
+        ("Left Shift", Scancode::LShift),
+        ("Right Shift", Scancode::RShift),
+        ("Left Ctrl", Scancode::LCtrl),
+        ("Right Ctrl", Scancode::RCtrl),
+        ("Left Alt", Scancode::LAlt),
+        ("Right Alt", Scancode::RAlt),
+        ("Home", Scancode::Home),
+        ("End", Scancode::End),
+        ("PageUp", Scancode::PageUp),
+        ("PageDown", Scancode::PageDown),
+        ("Insert", Scancode::Insert),
+        ("Delete", Scancode::Delete),
+        ("-", Scancode::Minus),
+        ("=", Scancode::Equals),
+        ("[", Scancode::LeftBracket),
+        ("]", Scancode::RightBracket),
+        (",", Scancode::Comma),
+        (".", Scancode::Period),
+        ("/", Scancode::Slash),
+        ("`", Scancode::Grave),
+    ];
+    for (name, scancode) in others {
+        names.push((name.to_string(), scancode));
+    }
+
+    return names;
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Modifiers {
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+}
+
+impl Modifiers {
+    fn from_keymod(keymod: &Mod) -> Modifiers {
+        return Modifiers {
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+            alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        };
+    }
+
+    fn from_keyboard(keyboard: &KeyboardState) -> Modifiers {
+        return Modifiers {
+            ctrl: keyboard.is_scancode_pressed(Scancode::LCtrl)
+                || keyboard.is_scancode_pressed(Scancode::RCtrl),
+            shift: keyboard.is_scancode_pressed(Scancode::LShift)
+                || keyboard.is_scancode_pressed(Scancode::RShift),
+            alt: keyboard.is_scancode_pressed(Scancode::LAlt)
+                || keyboard.is_scancode_pressed(Scancode::RAlt),
+        };
+    }
+
+    // A modifier key doesn't modify itself, Left Ctrl alone is not Ctrl+Left Ctrl
+    fn without_key(&self, scancode: Scancode) -> Modifiers {
+        return Modifiers {
+            ctrl: self.ctrl && !matches!(scancode, Scancode::LCtrl | Scancode::RCtrl),
+            shift: self.shift && !matches!(scancode, Scancode::LShift | Scancode::RShift),
+            alt: self.alt && !matches!(scancode, Scancode::LAlt | Scancode::RAlt),
+        };
+    }
+
+    fn contains(&self, other: &Modifiers) -> bool {
+        return (self.ctrl || !other.ctrl)
+            && (self.shift || !other.shift)
+            && (self.alt || !other.alt);
+    }
+}
+
+// A key with the modifiers held with it, written like "Ctrl+Shift+S"
+#[derive(Clone, Copy)]
+pub struct KeyBinding {
+    scancode: Scancode,
+    modifiers: Modifiers,
+}
+
+impl KeyBinding {
+    pub fn from_string(string: &str) -> Option<KeyBinding> {
+        let mut modifiers: Modifiers = Modifiers {
+            ctrl: false,
+            shift: false,
+            alt: false,
+        };
+        let mut parts: Vec<&str> = string.split('+').map(|part: &str| part.trim()).collect();
+        let key: &str = parts.pop()?;
+
+        for part in parts {
+            match part.to_lowercase().as_str() {
+                "ctrl" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" => modifiers.alt = true,
+                _ => return Option::None,
+            }
+        }
+
+        return get_named_scancodes()
+            .into_iter()
+            .find(|(name, _): &(String, Scancode)| name.eq_ignore_ascii_case(key))
+            .map(|(_, scancode): (String, Scancode)| KeyBinding {
+                scancode: scancode,
+                modifiers: modifiers,
+            });
+    }
+}
+
+// Same format as from_string, Ctrl+Shift+Alt+Key
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name: String = get_named_scancodes()
+            .into_iter()
+            .find(|(_, scancode): &(String, Scancode)| *scancode == self.scancode)
+            .map(|(name, _): (String, Scancode)| name)
+            .unwrap_or("?".to_string());
+
+        let mut string: String = String::new();
+        if self.modifiers.ctrl {
+            string += "Ctrl+";
+        }
+        if self.modifiers.shift {
+            string += "Shift+";
+        }
+        if self.modifiers.alt {
+            string += "Alt+";
+        }
+        return write!(f, "{}{}", string, name);
+    }
+}
+
+pub struct KeyBindings {
+    bindings: Vec<(Action, KeyBinding)>,
+}
+
+impl KeyBindings {
+    pub fn default() -> KeyBindings {
+        let defaults: [(Action, &str); 32] = [
+            (Action::MoveForward, "W"),
+            (Action::MoveBackward, "S"),
+            (Action::MoveLeft, "A"),
+            (Action::MoveRight, "D"),
+            (Action::MoveUp, "Space"),
+            (Action::MoveDown, "Left Shift"),
+            (Action::TurnLeft, "Left"),
+            (Action::TurnRight, "Right"),
+            (Action::LookUp, "Up"),
+            (Action::LookDown, "Down"),
+            (Action::RollLeft, "Q"),
+            (Action::RollRight, "E"),
+            (Action::SlowMode, "Left Ctrl"),
+            (Action::Quit, "Escape"),
+            (Action::ResetPosition, "R"),
+            (Action::SwitchAccumulation, "Return"),
+            (Action::FocusFarther, "F"),
+            (Action::FocusCloser, "C"),
+            (Action::SwitchVisibility, "V"),
+            (Action::SwitchPhysics, "P"),
+            (Action::SwitchDenoiser, "N"),
+            (Action::ReloadParameters, "Tab"),
+            (Action::RegenerateSpheres, "G"),
+            (Action::Screenshot, "F12"),
+            (Action::NextRenderPass, "B"),
+            (Action::SwitchMovementMode, "M"),
+            (Action::PlayCameraPath, "L"),
+            (Action::AddKeyframe, "K"),
+            (Action::ReleaseMouse, "Left Alt"),
+            (Action::SwitchHud, "F3"),
+            (Action::Help, "H"),
+            (Action::Help, "F1"),
+        ];
+
+        return KeyBindings {
+            bindings: defaults
+                .iter()
+                .map(|(action, key): &(Action, &str)| {
+                    (*action, KeyBinding::from_string(key).unwrap())
+                })
+                .collect(),
+        };
+    }
+
+    // Each action is bound to a key or a list of keys, the actions left out keep their keys
+    pub fn get_from_json(data: &Value, default: &KeyBindings) -> KeyBindings {
+        let mut bindings: Vec<(Action, KeyBinding)> = vec![];
+
+        for action in Action::ALL {
+            let keys: Vec<&str> = match &data[action.to_string()] {
+                Value::String(key) => vec![key.as_str()],
+                Value::Array(keys) => keys.iter().filter_map(|key: &Value| key.as_str()).collect(),
+                _ => {
+                    bindings.extend(
+                        default
+                            .bindings
+                            .iter()
+                            .filter(|(bound, _): &&(Action, KeyBinding)| *bound == action),
+                    );
+                    continue;
+                }
+            };
+
+            for key in keys {
+                match KeyBinding::from_string(key) {
+                    Some(binding) => bindings.push((action, binding)),
+                    None => println!("Unknown key \"{}\" for {}", key, action.to_string()),
+                }
+            }
+        }
+
+        return KeyBindings { bindings: bindings };
+    }
+
+    // The action of a key press, its modifiers have to be exactly the ones held
+    pub fn get_action(&self, scancode: Scancode, keymod: &Mod) -> Option<Action> {
+        let modifiers: Modifiers = Modifiers::from_keymod(keymod).without_key(scancode);
+
+        return self
+            .bindings
+            .iter()
+            .find(|(_, binding): &&(Action, KeyBinding)| {
+                binding.scancode == scancode && binding.modifiers == modifiers
+            })
+            .map(|(action, _): &(Action, KeyBinding)| *action);
+    }
+
+    // Actions held down, like the movements, still work with more modifiers held, to move while in slow mode
+    pub fn is_held(&self, action: Action, keyboard: &KeyboardState) -> bool {
+        let modifiers: Modifiers = Modifiers::from_keyboard(keyboard);
+
+        return self
+            .bindings
+            .iter()
+            .any(|(bound, binding): &(Action, KeyBinding)| {
+                *bound == action
+                    && keyboard.is_scancode_pressed(binding.scancode)
+                    && modifiers
+                        .without_key(binding.scancode)
+                        .contains(&binding.modifiers)
+            });
+    }
+
+    pub fn get_keys(&self, action: Action) -> Vec<String> {
+        return self
+            .bindings
+            .iter()
+            .filter(|(bound, _): &&(Action, KeyBinding)| *bound == action)
+            .map(|(_, binding): &(Action, KeyBinding)| binding.to_string())
+            .collect();
+    }
+
+    pub fn get_help_lines(&self) -> Vec<String> {
+        return Action::ALL
+            .iter()
+            .map(|action: &Action| {
+                format!(
+                    "{:<27}{}",
+                    action.get_description(),
+                    self.get_keys(*action).join(", ")
+                )
+            })
+            .collect();
+    }
+}
@@ -2,26 +2,51 @@ extern crate sdl2;
 
 use rand::rngs::ThreadRng;
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use std::{thread, time};
+use std::{env, thread, time};
 
+mod camera;
+mod camera_path;
+mod denoise;
+mod environment;
+mod filter;
+mod font;
 mod frame;
+mod headless;
+mod history;
+mod hud;
+mod input;
+mod key_bindings;
+mod noise;
+mod normal_map;
 mod observer;
+mod octree;
 mod parameters;
+mod physics;
 mod position;
 mod ray;
 mod ray_trace;
+mod render_pass;
 mod speed;
 mod sphere;
+mod stereo;
+mod texture;
+mod tile;
 mod util;
 mod vector;
 
+use crate::frame::Frame;
+use crate::hud::Hud;
+use crate::input::InputState;
+use crate::key_bindings::Action;
 use crate::observer::Observer;
 use crate::parameters::Parameters;
+use crate::physics::PhysicsClock;
 use crate::sphere::Sphere;
+use crate::stereo::StereoMode;
+use crate::tile::TileJob;
 
 mod display_ray_tracing;
-use display_ray_tracing::display;
+use display_ray_tracing::{display, display_tiles, draw_help};
 // mod display_2d;
 // use display_2d::display;
 
@@ -37,6 +62,8 @@ fn reload_params(
             observer.body.pos = obs.body.pos;
             observer.hor_angle = obs.hor_angle;
             observer.ver_angle = obs.ver_angle;
+            observer.roll_angle = obs.roll_angle;
+            observer.focus_distance = obs.focus_distance;
         }
         _ => {}
     };
@@ -56,6 +83,14 @@ fn generate_sphere_vector(params: &Parameters, rng: &mut ThreadRng) -> Vec<Spher
 }
 
 fn main() {
+    // offline render and recording, without any window
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(|arg: &String| arg.as_str()) {
+        Some("render") => return headless::render_command(&args),
+        Some("record") => return headless::record_command(&args),
+        _ => {}
+    }
+
     // init RNG
     let mut rng: ThreadRng = rand::thread_rng();
 
@@ -66,18 +101,33 @@ fn main() {
 
     let mut sphere_vector = generate_sphere_vector(&params, &mut rng);
 
+    // headless mode, a single frame written to the given file
+    match args.iter().position(|arg: &String| arg == "--output") {
+        Some(index) => {
+            let path: &str = args.get(index + 1).expect("--output needs a file path");
+            headless::render_to_file(
+                &mut observer,
+                &sphere_vector,
+                &params,
+                path,
+                args.contains(&"--passes".to_string()),
+            );
+            return;
+        }
+        None => {}
+    }
+
     // init video subsystem
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
     // open window and convert to canvas
+    let (output_width, output_height) = params.get_output_size();
     let window = video_subsystem
         .window(
             "CPU Raytracing",
-            ((params.ray_parameters.max_hor_value - params.ray_parameters.min_hor_value) as f64
-                * params.display_scale) as u32,
-            ((params.ray_parameters.max_ver_value - params.ray_parameters.min_ver_value) as f64
-                * params.display_scale) as u32,
+            (output_width as f64 * params.display_scale) as u32,
+            (output_height as f64 * params.display_scale) as u32,
         )
         .build()
         .unwrap();
@@ -90,148 +140,247 @@ fn main() {
 
     let mut event_pump = sdl_context.event_pump().unwrap();
 
+    // the mouse is captured to look around, it can be released to use other windows
+    let mouse = sdl_context.mouse();
+    let mut mouse_captured: bool = params.observer_parameters.mouse_look;
+    mouse.set_relative_mouse_mode(mouse_captured);
+
+    let mut last_frame: Option<Frame> = Option::None;
+    let mut screenshot_count: u64 = 0;
+    let mut tile_job: Option<TileJob> = Option::None;
+    let mut path_playback_start: Option<time::Instant> = Option::None;
+    let mut physics_clock: PhysicsClock = PhysicsClock::new();
+    let mut last_update: time::Instant = time::Instant::now();
+    let mut show_help: bool = false;
+    let mut show_hud: bool = params.hud_parameters.enabled;
+    let mut hud: Hud = Hud::new();
+
     // main loop
     'main_loop: loop {
+        // the mouse moves are summed up, the rays are generated once per frame
+        let mut mouse_motion: (i32, i32) = (0, 0);
+
         // check for key presses... Without this the window is unresponsive
         for event in event_pump.poll_iter() {
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => break 'main_loop,
-                Event::KeyDown {
-                    keycode: Some(Keycode::Up),
-                    ..
-                } => observer.turn_ver(
-                    params.observer_parameters.look_up_angle,
-                    &params.observer_parameters,
-                    &params.ray_parameters,
-                ),
-                Event::KeyDown {
-                    keycode: Some(Keycode::Down),
-                    ..
-                } => observer.turn_ver(
-                    params.observer_parameters.look_down_angle,
-                    &params.observer_parameters,
-                    &params.ray_parameters,
-                ),
-                Event::KeyDown {
-                    keycode: Some(Keycode::Left),
-                    ..
-                } => observer.turn_hor(
-                    params.observer_parameters.look_left_angle,
-                    &params.observer_parameters,
-                    &params.ray_parameters,
-                ),
-                Event::KeyDown {
-                    keycode: Some(Keycode::Right),
-                    ..
-                } => observer.turn_hor(
-                    params.observer_parameters.look_right_angle,
-                    &params.observer_parameters,
-                    &params.ray_parameters,
-                ),
-                Event::KeyDown {
-                    keycode: Some(Keycode::W),
-                    ..
-                } => observer.move_forward(
-                    params.observer_parameters.move_forward_distance,
-                    &params.observer_parameters,
-                    &params.ray_parameters,
-                ),
-                Event::KeyDown {
-                    keycode: Some(Keycode::S),
-                    ..
-                } => observer.move_forward(
-                    params.observer_parameters.move_backward_distance,
-                    &params.observer_parameters,
-                    &params.ray_parameters,
-                ),
-                Event::KeyDown {
-                    keycode: Some(Keycode::A),
-                    ..
-                } => observer.move_hor(
-                    params.observer_parameters.move_left_distance,
-                    &params.observer_parameters,
-                    &params.ray_parameters,
-                ),
-                Event::KeyDown {
-                    keycode: Some(Keycode::D),
-                    ..
-                } => observer.move_hor(
-                    params.observer_parameters.move_right_distance,
-                    &params.observer_parameters,
-                    &params.ray_parameters,
-                ),
-                Event::KeyDown {
-                    keycode: Some(Keycode::Space),
-                    ..
-                } => observer.move_ver(
-                    params.observer_parameters.move_up_distance,
-                    &params.observer_parameters,
-                    &params.ray_parameters,
-                ),
-                Event::KeyDown {
-                    keycode: Some(Keycode::LShift),
-                    ..
-                } => observer.move_ver(
-                    params.observer_parameters.move_down_distance,
-                    &params.observer_parameters,
+                Event::Quit { .. } => break 'main_loop,
+                Event::MouseMotion { xrel, yrel, .. } if mouse_captured => {
+                    mouse_motion = (mouse_motion.0 + xrel, mouse_motion.1 + yrel);
+                }
+                Event::MouseWheel { y, .. } => observer.scroll(
+                    y as f64,
+                    &mut params.observer_parameters,
                     &params.ray_parameters,
                 ),
-                Event::KeyDown {
-                    keycode: Some(Keycode::R),
-                    ..
-                } => observer.reset_position(&params.observer_parameters, &params.ray_parameters),
-                Event::KeyDown {
-                    keycode: Some(Keycode::Return),
-                    ..
-                } => observer.switch_accumulation_mode(),
-                Event::KeyDown {
-                    keycode: Some(Keycode::LCtrl),
-                    ..
-                } => observer.slow_speed_mode(),
-                Event::KeyUp {
-                    keycode: Some(Keycode::LCtrl),
-                    ..
-                } => observer.normal_speed_mode(),
-                Event::KeyDown {
-                    keycode: Some(Keycode::V),
-                    ..
-                } => observer.switch_visibility(),
-                Event::KeyDown {
-                    keycode: Some(Keycode::P),
-                    ..
-                } => params.physics_parameters.enabled = !params.physics_parameters.enabled,
-                Event::KeyDown {
-                    keycode: Some(Keycode::Tab),
-                    ..
-                } => {
-                    (params, observer) = reload_params(Option::Some(params), Option::Some(observer))
+                Event::MouseButtonDown { .. }
+                    if params.observer_parameters.mouse_look && !mouse_captured =>
+                {
+                    mouse_captured = true;
+                    mouse.set_relative_mouse_mode(mouse_captured);
                 }
+                // the keys are bound to actions in the parameters, the keys held down are read below
                 Event::KeyDown {
-                    keycode: Some(Keycode::G),
+                    scancode: Some(scancode),
+                    keymod,
+                    repeat,
                     ..
-                } => sphere_vector = generate_sphere_vector(&params, &mut rng),
+                } => match params
+                    .key_bindings
+                    .get_action(scancode, &keymod)
+                    .filter(|action: &Action| !repeat || action.is_repeatable())
+                {
+                    // quitting stops a tiled render first, it quits when there is nothing to stop
+                    Some(Action::Quit) => match &mut tile_job {
+                        Some(job) if job.is_running() => job.cancel(),
+                        _ => break 'main_loop,
+                    },
+                    Some(Action::ReleaseMouse) if params.observer_parameters.mouse_look => {
+                        mouse_captured = !mouse_captured;
+                        mouse.set_relative_mouse_mode(mouse_captured);
+                    }
+                    Some(Action::ResetPosition) => {
+                        observer.reset_position(&params.observer_parameters, &params.ray_parameters)
+                    }
+                    Some(Action::SwitchAccumulation) => observer.switch_accumulation_mode(),
+                    Some(Action::FocusFarther) => observer.change_focus(
+                        params.observer_parameters.focus_step_factor,
+                        &params.observer_parameters,
+                        &params.ray_parameters,
+                    ),
+                    Some(Action::FocusCloser) => observer.change_focus(
+                        1. / params.observer_parameters.focus_step_factor,
+                        &params.observer_parameters,
+                        &params.ray_parameters,
+                    ),
+                    Some(Action::SwitchVisibility) => observer.switch_visibility(),
+                    Some(Action::SwitchPhysics) => {
+                        params.physics_parameters.enabled = !params.physics_parameters.enabled
+                    }
+                    Some(Action::SwitchDenoiser) => {
+                        params.denoiser_parameters.enabled = !params.denoiser_parameters.enabled
+                    }
+                    Some(Action::ReloadParameters) => {
+                        (params, observer) =
+                            reload_params(Option::Some(params), Option::Some(observer));
+                        tile_job = Option::None;
+                    }
+                    Some(Action::RegenerateSpheres) => {
+                        sphere_vector = generate_sphere_vector(&params, &mut rng);
+                        tile_job = Option::None;
+                    }
+                    Some(Action::Screenshot) => match &last_frame {
+                        Some(frame) => {
+                            frame.save_passes(
+                                &format!("screenshot_{}.png", screenshot_count),
+                                params.ray_parameters.bounce_count,
+                            );
+                            screenshot_count += 1;
+                        }
+                        None => {}
+                    },
+                    Some(Action::NextRenderPass) => observer.next_render_pass(),
+                    Some(Action::SwitchMovementMode) => observer.switch_movement_mode(),
+                    Some(Action::PlayCameraPath) => {
+                        path_playback_start = match path_playback_start {
+                            Some(_) => Option::None,
+                            None if params.camera_path.keyframes.is_empty() => {
+                                println!("The camera path has no keyframes");
+                                Option::None
+                            }
+                            None => Option::Some(time::Instant::now()),
+                        }
+                    }
+                    Some(Action::AddKeyframe) => {
+                        params.camera_path.add_keyframe(
+                            observer.body.pos,
+                            observer.hor_angle,
+                            observer.ver_angle,
+                        );
+                        println!(
+                            "Keyframe {} added at {:.2}s",
+                            params.camera_path.keyframes.len(),
+                            params.camera_path.duration()
+                        );
+                        match std::fs::write(
+                            "camera_path.json",
+                            serde_json::to_string_pretty(&params.camera_path.to_json()).unwrap(),
+                        ) {
+                            Ok(_) => {}
+                            Err(e) => println!("Unable to save camera_path.json: {}", e),
+                        }
+                    }
+                    // a tiled frame only redraws its new tiles, it is started again to clear an overlay
+                    Some(Action::SwitchHud) => {
+                        show_hud = !show_hud;
+                        if !show_hud {
+                            tile_job = Option::None;
+                        }
+                    }
+                    Some(Action::Help) => {
+                        show_help = !show_help;
+                        if !show_help {
+                            tile_job = Option::None;
+                        }
+                    }
+                    _ => {}
+                },
                 _ => {}
             }
         }
 
+        // movement from the keys held down, scaled by the time since the last frame
+        let now: time::Instant = time::Instant::now();
+        let dt: f64 = now.duration_since(last_update).as_secs_f64().min(0.1);
+        last_update = now;
+        observer.frame_interval = dt;
+        let input: InputState =
+            InputState::from_keyboard(&event_pump.keyboard_state(), &params.key_bindings);
+
         // physics
         if params.physics_parameters.enabled {
-            for s in sphere_vector.iter_mut() {
-                s.physics(&params.physics_parameters);
+            physics_clock.update(&mut sphere_vector, &params.physics_parameters);
+        } else {
+            physics_clock.pause();
+        }
+
+        // camera path playback, the observer is moved to the pose of the path at the elapsed time,
+        // otherwise it moves with the keys, walks and collides with the scene
+        match path_playback_start {
+            Some(start) => {
+                let elapsed: f64 = start.elapsed().as_secs_f64();
+                match params.camera_path.get_pose(elapsed) {
+                    Some(pose) => observer.set_pose(
+                        &pose,
+                        &params.observer_parameters,
+                        &params.ray_parameters,
+                    ),
+                    None => {}
+                }
+                if elapsed >= params.camera_path.duration() {
+                    path_playback_start = Option::None;
+                }
+            }
+            None => {
+                if mouse_motion != (0, 0) {
+                    observer.look(
+                        mouse_motion.0 as f64,
+                        mouse_motion.1 as f64,
+                        &params.observer_parameters,
+                        &params.ray_parameters,
+                    );
+                }
+                observer.update_movement(
+                    &input,
+                    dt,
+                    &params.observer_parameters,
+                    &params.ray_parameters,
+                );
+                observer.update_body(
+                    dt,
+                    &sphere_vector,
+                    &params.observer_parameters,
+                    &params.physics_parameters,
+                    &params.ray_parameters,
+                );
             }
         }
 
-        // draw and refresh the canvas display
-        display(
-            &mut observer,
-            &sphere_vector,
-            &params.ray_parameters,
-            &mut canvas,
-        );
+        // draw and refresh the canvas display, a tiled frame can take several iterations
+        if params.tile_parameters.enabled
+            && matches!(params.observer_parameters.stereo_mode, StereoMode::Off)
+        {
+            match display_tiles(
+                &mut observer,
+                &mut tile_job,
+                &sphere_vector,
+                &params,
+                &mut canvas,
+            ) {
+                Some(frame) => {
+                    last_frame = Option::Some(frame);
+                    hud.update(
+                        observer.get_rays_traced(),
+                        params.hud_parameters.update_interval_ms,
+                    );
+                }
+                None => {}
+            }
+        } else {
+            last_frame = Option::Some(display(&mut observer, &sphere_vector, &params, &mut canvas));
+            hud.update(
+                observer.get_rays_traced(),
+                params.hud_parameters.update_interval_ms,
+            );
+        }
+
+        // overlays, drawn over the image
+        if show_hud {
+            hud.draw(&observer, &last_frame, &sphere_vector, &params, &mut canvas);
+        }
+        if show_help {
+            draw_help(&params.key_bindings, &mut canvas);
+        }
+        canvas.present();
 
         // sleep between frames
         thread::sleep(time::Duration::from_millis(params.frame_period_ms));
@@ -0,0 +1,103 @@
+use crate::position::Position;
+
+const GRADIENTS: [(f64, f64, f64); 12] = [
+    (1., 1., 0.),
+    (-1., 1., 0.),
+    (1., -1., 0.),
+    (-1., -1., 0.),
+    (1., 0., 1.),
+    (-1., 0., 1.),
+    (1., 0., -1.),
+    (-1., 0., -1.),
+    (0., 1., 1.),
+    (0., -1., 1.),
+    (0., 1., -1.),
+    (0., -1., -1.),
+];
+
+fn hash(x: i64, y: i64, z: i64) -> usize {
+    let mut h: u64 = (x as u64).wrapping_mul(0x8da6b343)
+        ^ (y as u64).wrapping_mul(0xd8163841)
+        ^ (z as u64).wrapping_mul(0xcb1ab31f);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1e995);
+    h ^= h >> 15;
+    return (h % GRADIENTS.len() as u64) as usize;
+}
+
+fn fade(t: f64) -> f64 {
+    return t * t * t * (t * (t * 6. - 15.) + 10.);
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    return a + t * (b - a);
+}
+
+fn corner(x: i64, y: i64, z: i64, dx: f64, dy: f64, dz: f64) -> f64 {
+    let (gx, gy, gz) = GRADIENTS[hash(x, y, z)];
+    return (gx * dx) + (gy * dy) + (gz * dz);
+}
+
+// Gradient (Perlin) noise, roughly in [-1, 1]
+pub fn perlin(p: &Position) -> f64 {
+    let (x0, y0, z0) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let (dx, dy, dz) = (p.x - x0, p.y - y0, p.z - z0);
+    let (x, y, z) = (x0 as i64, y0 as i64, z0 as i64);
+    let (u, v, w) = (fade(dx), fade(dy), fade(dz));
+
+    return lerp(
+        w,
+        lerp(
+            v,
+            lerp(
+                u,
+                corner(x, y, z, dx, dy, dz),
+                corner(x + 1, y, z, dx - 1., dy, dz),
+            ),
+            lerp(
+                u,
+                corner(x, y + 1, z, dx, dy - 1., dz),
+                corner(x + 1, y + 1, z, dx - 1., dy - 1., dz),
+            ),
+        ),
+        lerp(
+            v,
+            lerp(
+                u,
+                corner(x, y, z + 1, dx, dy, dz - 1.),
+                corner(x + 1, y, z + 1, dx - 1., dy, dz - 1.),
+            ),
+            lerp(
+                u,
+                corner(x, y + 1, z + 1, dx, dy - 1., dz - 1.),
+                corner(x + 1, y + 1, z + 1, dx - 1., dy - 1., dz - 1.),
+            ),
+        ),
+    );
+}
+
+// Sum of several octaves of perlin noise, each one twice as detailed and half as strong
+pub fn fractal(p: &Position, octaves: u64) -> f64 {
+    let mut total: f64 = 0.;
+    let mut frequency: f64 = 1.;
+    let mut amplitude: f64 = 1.;
+
+    for _ in 0..octaves {
+        total += perlin(&p.scaled(frequency)) * amplitude;
+        frequency *= 2.;
+        amplitude *= 0.5;
+    }
+
+    return total;
+}
+
+pub fn fractal_gradient(p: &Position, octaves: u64) -> Position {
+    let e: f64 = 0.001;
+    let center: f64 = fractal(p, octaves);
+
+    return Position {
+        x: (fractal(&(*p + Position { x: e, y: 0., z: 0. }), octaves) - center) / e,
+        y: (fractal(&(*p + Position { x: 0., y: e, z: 0. }), octaves) - center) / e,
+        z: (fractal(&(*p + Position { x: 0., y: 0., z: e }), octaves) - center) / e,
+    };
+}
@@ -0,0 +1,100 @@
+use crate::{noise, position::Position, texture::Texture};
+
+#[derive(Clone, Copy)]
+pub enum NormalMapType {
+    None,
+    Noise,
+    Image,
+}
+
+impl NormalMapType {
+    pub fn to_string(&self) -> &str {
+        return match *self {
+            Self::None => "None",
+            Self::Noise => "Noise",
+            Self::Image => "Image",
+        };
+    }
+
+    pub fn from_string(string: &str) -> Self {
+        return match string {
+            "None" => Self::None,
+            "Noise" => Self::Noise,
+            "Image" => Self::Image,
+            _ => Self::None,
+        };
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct NormalMap {
+    pub type_: NormalMapType,
+    pub strength: f64,
+    pub scale: f64,
+    pub octaves: u64,
+    pub texture_index: u64,
+}
+
+impl NormalMap {
+    pub fn none() -> NormalMap {
+        return NormalMap {
+            type_: NormalMapType::None,
+            strength: 0.,
+            scale: 1.,
+            octaves: 1,
+            texture_index: 0,
+        };
+    }
+
+    // normal: unit normal of the smooth sphere, local: hit point relative to the sphere center
+    pub fn perturb(
+        &self,
+        normal: Position,
+        local: Position,
+        textures: &Vec<Option<Texture>>,
+    ) -> Position {
+        return match self.type_ {
+            NormalMapType::None => normal,
+            NormalMapType::Noise => self.perturb_noise(normal, local),
+            NormalMapType::Image => match textures.get(self.texture_index as usize) {
+                Some(Some(texture)) => self.perturb_image(normal, texture),
+                _ => normal,
+            },
+        };
+    }
+
+    fn perturb_noise(&self, normal: Position, local: Position) -> Position {
+        let gradient: Position = noise::fractal_gradient(&local.scaled(self.scale), self.octaves);
+        let tangential: Position = gradient - normal.scaled(gradient.dot(&normal));
+
+        return (normal - tangential.scaled(self.strength)).normalized();
+    }
+
+    fn perturb_image(&self, normal: Position, texture: &Texture) -> Position {
+        let u: f64 = 0.5 + normal.y.atan2(normal.x) / (2. * std::f64::consts::PI);
+        let v: f64 = normal.z.clamp(-1., 1.).acos() / std::f64::consts::PI;
+        let [r, g, b] = texture.sample((u * self.scale).fract(), (v * self.scale).fract());
+
+        // Tangent space, tangent along the longitude and bitangent along the latitude
+        let up: Position = if normal.z.abs() > 0.999 {
+            Position {
+                x: 1.,
+                y: 0.,
+                z: 0.,
+            }
+        } else {
+            Position {
+                x: 0.,
+                y: 0.,
+                z: 1.,
+            }
+        };
+        let tangent: Position = up.cross(&normal).normalized();
+        let bitangent: Position = normal.cross(&tangent);
+
+        return (tangent.scaled((r * 2. - 1.) * self.strength)
+            + bitangent.scaled((g * 2. - 1.) * self.strength)
+            + normal.scaled(b * 2. - 1.))
+        .normalized();
+    }
+}
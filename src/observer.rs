@@ -1,22 +1,106 @@
 use crate::{
-    frame::Frame,
-    parameters::{ObserverParameters, Parameters, RayParameters},
+    camera::{Camera, ProjectionMode},
+    camera_path::Keyframe,
+    frame::{Frame, FrameAccumulator},
+    history::History,
+    input::InputState,
+    parameters::{ObserverParameters, Parameters, PhysicsParameters, RayParameters},
     position::Position,
     ray::Ray,
     ray_trace::RayTrace,
+    render_pass::RenderPass,
     speed::Speed,
     sphere::Sphere,
+    stereo::{self, StereoMode},
+    tile::Tile,
+    util,
 };
+use rand::{rngs::SmallRng, Rng};
 use rayon::prelude::*;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+// Number of passes pushing the observer out of the spheres, a push can lead into another sphere
+const COLLISION_ITERATIONS: u64 = 3;
+
+#[derive(Clone, Copy)]
+pub enum MovementMode {
+    Fly,
+    Walk,
+}
+
+impl MovementMode {
+    pub fn to_string(&self) -> &str {
+        return match *self {
+            Self::Fly => "Fly",
+            Self::Walk => "Walk",
+        };
+    }
+
+    pub fn from_string(string: &str) -> Self {
+        return match string {
+            "Fly" => Self::Fly,
+            "Walk" => Self::Walk,
+            _ => Self::Fly,
+        };
+    }
+}
+
+// What the mouse wheel changes
+#[derive(Clone, Copy)]
+pub enum ScrollMode {
+    Speed,
+    Fov,
+}
+
+impl ScrollMode {
+    pub fn to_string(&self) -> &str {
+        return match *self {
+            Self::Speed => "Speed",
+            Self::Fov => "Fov",
+        };
+    }
+
+    pub fn from_string(string: &str) -> Self {
+        return match string {
+            "Speed" => Self::Speed,
+            "Fov" => Self::Fov,
+            _ => Self::Speed,
+        };
+    }
+}
 
 pub struct Observer {
     pub body: Sphere,
     pub hor_angle: f64,
     pub ver_angle: f64,
+    pub roll_angle: f64,
+    pub focus_distance: f64,
     pub rays: Vec<Ray>,
     pub accumulation_mode: bool,
-    frame_stack: Vec<Frame>,
+    pub render_pass: RenderPass,
+    history: History,
     slow_speed_mode: bool,
+    previous_camera: Option<Camera>,
+    // Seconds between the previous camera and the current one, the camera blur covers the shutter interval of it
+    pub frame_interval: f64,
+    sphere_motion_blur: bool,
+    eye_offset: f64,
+    render_scale: f64,
+    last_render_time_ms: f64,
+    idle_frames: u64,
+    idle_accumulation: bool,
+    rays_generation: u64,
+    rays_traced: AtomicU64,
+    movement_mode: MovementMode,
+    grounded: bool,
+    velocity: Position,
+    speed_factor: f64,
+    // With a seed, the same frames give the same samples
+    pub seed: Option<u64>,
+    frame_count: u64,
 }
 
 impl Observer {
@@ -24,40 +108,184 @@ impl Observer {
         let mut obs = Observer {
             hor_angle: 0.,
             ver_angle: 0.,
+            roll_angle: 0.,
+            focus_distance: parameters.observer_parameters.focus_distance,
             rays: Vec::new(),
             accumulation_mode: false,
-            frame_stack: Vec::new(),
+            render_pass: parameters.observer_parameters.default_render_pass,
+            history: History::new(),
             slow_speed_mode: false,
+            previous_camera: Option::None,
+            frame_interval: 0.,
+            sphere_motion_blur: false,
+            eye_offset: 0.,
+            render_scale: 1.,
+            last_render_time_ms: 0.,
+            idle_frames: 0,
+            idle_accumulation: false,
+            rays_generation: 0,
+            rays_traced: AtomicU64::new(0),
+            movement_mode: parameters.observer_parameters.movement_mode,
+            grounded: false,
+            velocity: Position {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            speed_factor: 1.,
+            seed: Option::None,
+            frame_count: 0,
             body: parameters.observer_parameters.default_body.clone(),
         };
         obs.generate_rays(&parameters.ray_parameters, &parameters.observer_parameters);
         return obs;
     }
 
+    pub fn get_forward(&self) -> Position {
+        return Position {
+            x: self.hor_angle.cos() * self.ver_angle.cos(),
+            y: self.hor_angle.sin() * self.ver_angle.cos(),
+            z: self.ver_angle.sin(),
+        };
+    }
+
+    pub fn get_camera(
+        &self,
+        ray_parameters: &RayParameters,
+        observer_parameters: &ObserverParameters,
+    ) -> Camera {
+        let width: f64 = (ray_parameters.max_hor_value - ray_parameters.min_hor_value) as f64;
+        let height: f64 = (ray_parameters.max_ver_value - ray_parameters.min_ver_value) as f64;
+
+        return Camera::new(
+            self.body.pos,
+            self.body.pos + self.get_forward(),
+            observer_parameters.up_vector,
+            observer_parameters.vertical_fov,
+            if observer_parameters.aspect_ratio > 0. {
+                observer_parameters.aspect_ratio
+            } else {
+                width / height
+            },
+            self.roll_angle,
+            observer_parameters.aperture_radius,
+            self.focus_distance,
+        )
+        .with_projection(
+            observer_parameters.projection,
+            observer_parameters.orthographic_height,
+            observer_parameters.fisheye_fov,
+        );
+    }
+
+    // Size of the rendered image, smaller than the displayed one when the render scale is lowered
+    fn get_render_size(&self, ray_parameters: &RayParameters) -> (i64, i64) {
+        let width: i64 = ray_parameters.max_hor_value - ray_parameters.min_hor_value;
+        let height: i64 = ray_parameters.max_ver_value - ray_parameters.min_ver_value;
+
+        return (
+            ((width as f64 * self.render_scale).ceil() as i64).clamp(1, width),
+            ((height as f64 * self.render_scale).ceil() as i64).clamp(1, height),
+        );
+    }
+
+    // Stratified sub-pixel offsets in [-0.5, 0.5], the pixel center when jitter is off
+    fn get_sample_offsets<R: Rng>(sample_count: u64, jitter: bool, rng: &mut R) -> Vec<(f64, f64)> {
+        if sample_count == 0 {
+            return vec![];
+        }
+
+        let columns: u64 = (sample_count as f64).sqrt().ceil() as u64;
+        let rows: u64 = (sample_count + columns - 1) / columns;
+
+        return (0..sample_count)
+            .map(|i: u64| {
+                let (jitter_x, jitter_y): (f64, f64) = if jitter {
+                    (rng.gen(), rng.gen())
+                } else {
+                    (0.5, 0.5)
+                };
+                (
+                    ((i % columns) as f64 + jitter_x) / columns as f64 - 0.5,
+                    ((i / columns) as f64 + jitter_y) / rows as f64 - 0.5,
+                )
+            })
+            .collect();
+    }
+
     fn generate_rays(
         &mut self,
         ray_parameters: &RayParameters,
         observer_parameters: &ObserverParameters,
     ) {
         self.rays.clear();
+        self.rays_generation += 1;
 
-        for x in ray_parameters.min_hor_value..ray_parameters.max_hor_value {
-            for y in ray_parameters.min_ver_value..ray_parameters.max_ver_value {
-                let r = Ray::new_turned(
-                    self.body.pos.clone(),
-                    Position {
-                        x: observer_parameters.look_vector_distance,
-                        y: x as f64,
-                        z: y as f64,
-                    },
-                    1.,
-                    x - ray_parameters.min_hor_value,
-                    y - ray_parameters.min_ver_value,
-                    0.,
-                    self.ver_angle,
-                    self.hor_angle,
-                );
-                self.rays.push(r);
+        let mut rng: SmallRng = util::get_rng(
+            self.seed
+                .map(|seed: u64| util::mix_seed(seed, self.rays_generation)),
+        );
+        let camera: Camera = self.get_camera(ray_parameters, observer_parameters);
+        let (render_width, render_height) = self.get_render_size(ray_parameters);
+        let width: f64 = render_width as f64;
+        let height: f64 = render_height as f64;
+        let sample_count: u64 = ray_parameters.samples_per_pixel.max(1);
+        let jitter: bool = sample_count > 1 || self.accumulation_mode;
+        let shutter_interval: f64 = observer_parameters.shutter_interval;
+
+        // The samples only go to the noisy pixels once there is an history of this exact image
+        let adaptive: bool = self.accumulation_mode
+            && observer_parameters.adaptive_sampling
+            && self.render_scale >= 1.
+            && matches!(observer_parameters.stereo_mode, StereoMode::Off)
+            && self.history.is_seen_from(&camera);
+
+        for x in 0..render_width {
+            for y in 0..render_height {
+                let pixel_sample_count: u64 = if adaptive {
+                    self.history.get_adaptive_sample_count(
+                        &(x, y),
+                        sample_count,
+                        observer_parameters.adaptive_error_threshold,
+                        observer_parameters.adaptive_min_samples,
+                        observer_parameters.adaptive_max_samples_per_frame,
+                        observer_parameters.max_history_frames,
+                    )
+                } else {
+                    sample_count
+                };
+
+                for (x_offset, y_offset) in
+                    Self::get_sample_offsets(pixel_sample_count, jitter, &mut rng)
+                {
+                    // Each sample is taken at a random time during the shutter interval before now
+                    let time: f64 = if shutter_interval > 0. {
+                        -rng.gen::<f64>() * shutter_interval
+                    } else {
+                        0.
+                    };
+                    // The camera moves linearly between the two poses, as far back as the spheres do
+                    let mut sample_camera: Camera = match &self.previous_camera {
+                        Some(previous) if shutter_interval > 0. && self.frame_interval > 0. => {
+                            Camera::lerp(previous, &camera, 1. + time / self.frame_interval)
+                        }
+                        _ => camera,
+                    };
+                    sample_camera.origin =
+                        sample_camera.origin + sample_camera.right.scaled(self.eye_offset);
+
+                    let mut ray: Ray = sample_camera.get_ray(
+                        (x as f64 + 0.5 + x_offset) / width,
+                        (y as f64 + 0.5 + y_offset) / height,
+                        x,
+                        y,
+                        &mut rng,
+                    );
+                    ray.x_offset = x_offset;
+                    ray.y_offset = y_offset;
+                    ray.time = if self.sphere_motion_blur { time } else { 0. };
+                    self.rays.push(ray);
+                }
             }
         }
     }
@@ -65,13 +293,32 @@ impl Observer {
     fn generate_ray_traces(&self, ray_parameters: &RayParameters) -> Vec<RayTrace> {
         let mut ray_traces: Vec<RayTrace> = Vec::new();
 
-        self.rays.iter().for_each(|ray| {
-            ray_traces.push(RayTrace::new(ray, ray_parameters));
+        self.rays.iter().enumerate().for_each(|(index, ray)| {
+            ray_traces.push(self.new_ray_trace(index, ray, ray_parameters));
         });
 
         return ray_traces;
     }
 
+    fn new_ray_trace<'a>(
+        &self,
+        index: usize,
+        ray: &'a Ray,
+        ray_parameters: &RayParameters,
+    ) -> RayTrace<'a> {
+        let mut trace: RayTrace = RayTrace::new(ray, ray_parameters);
+        trace.sample_environment_light =
+            self.accumulation_mode && ray_parameters.environment.sample_as_light;
+        // One stream per ray of each frame, whatever the order in which the workers trace them
+        trace.seed = self.seed.map(|seed: u64| {
+            util::mix_seed(
+                util::mix_seed(util::mix_seed(seed, self.frame_count), self.rays_generation),
+                index as u64,
+            )
+        });
+        return trace;
+    }
+
     fn trace_parallel(
         &self,
         ray_parameters: &RayParameters,
@@ -85,28 +332,312 @@ impl Observer {
             .for_each(|trace: &mut RayTrace<'_>| {
                 trace.trace(sphere_vector, ray_parameters);
             });
+        self.count_rays_traced(&ray_traces);
 
         return ray_traces;
     }
 
-    pub fn get_next_frame(
+    // The primary rays and one more for each bounce
+    fn count_rays_traced(&self, traces: &Vec<RayTrace>) {
+        let count: u64 = traces
+            .iter()
+            .map(|trace: &RayTrace| 1 + trace.bounces)
+            .sum();
+        self.rays_traced.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn get_rays_traced(&self) -> u64 {
+        return self.rays_traced.load(Ordering::Relaxed);
+    }
+
+    // Focuses on whatever is under the center of the screen, keeps the last distance otherwise
+    fn autofocus(
+        &mut self,
+        ray_parameters: &RayParameters,
+        observer_parameters: &ObserverParameters,
+        sphere_vector: &Vec<&Sphere>,
+    ) {
+        let camera: Camera = self.get_camera(ray_parameters, observer_parameters);
+        let center_ray: Ray = Ray::new(
+            camera.origin,
+            camera.origin + camera.get_direction(0.5, 0.5),
+            1.,
+            0,
+            0,
+        );
+
+        // The center ray starts inside the observer's own body, a copy of it is among the spheres
+        let others: Vec<&Sphere> = sphere_vector
+            .iter()
+            .filter(|sphere: &&&Sphere| {
+                sphere.pos.dist(&self.body.pos) > 0. || sphere.radius != self.body.radius
+            })
+            .copied()
+            .collect();
+
+        match center_ray.find_collision(&others, ray_parameters) {
+            Some(((factor, _), _)) => {
+                self.focus_distance = factor;
+            }
+            None => {}
+        }
+    }
+
+    fn update_rays(
+        &mut self,
+        eye_offset: f64,
+        ray_parameters: &RayParameters,
+        observer_parameters: &ObserverParameters,
+    ) {
+        // The lens, the sub-pixel positions and the time are sampled again for every frame
+        if eye_offset != self.eye_offset
+            || observer_parameters.aperture_radius > 0.
+            || observer_parameters.shutter_interval > 0.
+            || ray_parameters.samples_per_pixel > 1
+            || self.accumulation_mode
+        {
+            self.eye_offset = eye_offset;
+            self.generate_rays(ray_parameters, observer_parameters);
+        }
+    }
+
+    fn render_eye(
         &mut self,
+        eye_offset: f64,
         ray_parameters: &RayParameters,
+        observer_parameters: &ObserverParameters,
         sphere_vector: &Vec<&Sphere>,
     ) -> Frame {
+        self.update_rays(eye_offset, ray_parameters, observer_parameters);
+
         let traces: Vec<RayTrace> = self.trace_parallel(ray_parameters, sphere_vector);
+        let (width, height) = self.get_render_size(ray_parameters);
+        let frame: Frame = Frame::create_from_ray_trace(traces, width, height, ray_parameters);
 
-        let frame: Frame = Frame::create_from_ray_trace(traces);
+        if self.render_scale < 1. {
+            return frame.resized(
+                ray_parameters.max_hor_value - ray_parameters.min_hor_value,
+                ray_parameters.max_ver_value - ray_parameters.min_ver_value,
+            );
+        }
 
-        if self.accumulation_mode {
-            self.frame_stack.push(frame);
+        return frame;
+    }
 
-            return Frame::accumulate_frames(&self.frame_stack);
+    // Lowers the resolution while moving to keep up with the target frame time, full resolution when idle
+    fn update_render_scale(
+        &mut self,
+        ray_parameters: &RayParameters,
+        observer_parameters: &ObserverParameters,
+    ) {
+        let moving: bool = match &self.previous_camera {
+            Some(previous) => {
+                !previous.has_same_pose(&self.get_camera(ray_parameters, observer_parameters))
+            }
+            None => false,
+        };
+        let previous_scale: f64 = self.render_scale;
+
+        if moving {
+            self.idle_frames = 0;
+
+            if self.idle_accumulation {
+                self.idle_accumulation = false;
+                self.switch_accumulation_mode();
+            }
+
+            if observer_parameters.adaptive_resolution && self.last_render_time_ms > 0. {
+                // The render time goes with the pixel count, so with the square of the scale
+                let ratio: f64 = (observer_parameters.target_frame_time_ms
+                    / self.last_render_time_ms)
+                    .sqrt()
+                    .clamp(0.5, 2.);
+                self.render_scale = (self.render_scale * ratio)
+                    .clamp(observer_parameters.min_render_scale.min(1.), 1.);
+            }
+        } else {
+            self.idle_frames += 1;
+            self.render_scale = 1.;
+
+            if observer_parameters.accumulate_when_idle
+                && !self.accumulation_mode
+                && self.idle_frames >= observer_parameters.idle_frames_before_accumulation
+            {
+                self.switch_accumulation_mode();
+                self.idle_accumulation = true;
+            }
+        }
+
+        if !observer_parameters.adaptive_resolution {
+            self.render_scale = 1.;
+        }
+
+        if self.render_scale != previous_scale {
+            self.generate_rays(ray_parameters, observer_parameters);
+        }
+    }
+
+    pub fn get_next_frame(
+        &mut self,
+        ray_parameters: &RayParameters,
+        observer_parameters: &ObserverParameters,
+        sphere_vector: &Vec<&Sphere>,
+        physics_enabled: bool,
+    ) -> Frame {
+        self.prepare_frame(
+            ray_parameters,
+            observer_parameters,
+            sphere_vector,
+            physics_enabled,
+        );
+
+        let start: Instant = Instant::now();
+
+        let frame: Frame = match observer_parameters.stereo_mode {
+            StereoMode::Off => {
+                self.render_eye(0., ray_parameters, observer_parameters, sphere_vector)
+            }
+            mode => {
+                let half_distance: f64 = observer_parameters.interpupillary_distance / 2.;
+                let left: Frame = self.render_eye(
+                    -half_distance,
+                    ray_parameters,
+                    observer_parameters,
+                    sphere_vector,
+                );
+                let right: Frame = self.render_eye(
+                    half_distance,
+                    ray_parameters,
+                    observer_parameters,
+                    sphere_vector,
+                );
+                stereo::combine(&left, &right, mode)
+            }
+        };
+
+        self.last_render_time_ms = start.elapsed().as_secs_f64() * 1000.;
+
+        return self.finish_frame(frame, ray_parameters, observer_parameters);
+    }
+
+    fn prepare_frame(
+        &mut self,
+        ray_parameters: &RayParameters,
+        observer_parameters: &ObserverParameters,
+        sphere_vector: &Vec<&Sphere>,
+        physics_enabled: bool,
+    ) {
+        self.frame_count += 1;
+
+        // Spheres only move, and so only blur, when the physics are running
+        self.sphere_motion_blur = physics_enabled;
+
+        self.update_render_scale(ray_parameters, observer_parameters);
+
+        if observer_parameters.aperture_radius > 0. && observer_parameters.autofocus {
+            self.autofocus(ray_parameters, observer_parameters, sphere_vector);
+        }
+    }
+
+    fn finish_frame(
+        &mut self,
+        frame: Frame,
+        ray_parameters: &RayParameters,
+        observer_parameters: &ObserverParameters,
+    ) -> Frame {
+        let camera: Camera = self.get_camera(ray_parameters, observer_parameters);
+        self.previous_camera = Option::Some(camera);
+
+        // Low resolution frames would only blur the history, they are shown as they are
+        if self.accumulation_mode && self.render_scale >= 1. {
+            // The combined stereo image is not the image of a single camera, it can't be reprojected
+            let reproject: bool = observer_parameters.temporal_reprojection
+                && matches!(observer_parameters.stereo_mode, StereoMode::Off);
+
+            return self.history.accumulate(
+                &frame,
+                &camera,
+                reproject,
+                observer_parameters.max_history_frames,
+                observer_parameters.max_reprojected_frames,
+                observer_parameters.reprojection_depth_tolerance,
+            );
         }
 
         return frame;
     }
 
+    pub fn get_rays_generation(&self) -> u64 {
+        return self.rays_generation;
+    }
+
+    // Same as get_next_frame, but the rays are only generated here and traced later, tile by tile
+    pub fn start_tiled_frame(
+        &mut self,
+        ray_parameters: &RayParameters,
+        observer_parameters: &ObserverParameters,
+        sphere_vector: &Vec<&Sphere>,
+        physics_enabled: bool,
+    ) -> (i64, i64) {
+        self.prepare_frame(
+            ray_parameters,
+            observer_parameters,
+            sphere_vector,
+            physics_enabled,
+        );
+
+        // The tiles are streamed to the display as they are, at full resolution
+        if self.render_scale < 1. {
+            self.render_scale = 1.;
+            self.generate_rays(ray_parameters, observer_parameters);
+        }
+        self.update_rays(0., ray_parameters, observer_parameters);
+
+        return self.get_render_size(ray_parameters);
+    }
+
+    // Each tile is traced by one worker of the pool
+    pub fn trace_tiles(
+        &self,
+        tiles: &Vec<(Tile, &Vec<usize>)>,
+        ray_parameters: &RayParameters,
+        sphere_vector: &Vec<&Sphere>,
+    ) -> Vec<FrameAccumulator> {
+        let (width, height) = self.get_render_size(ray_parameters);
+
+        return tiles
+            .par_iter()
+            .map(|(_, ray_indices): &(Tile, &Vec<usize>)| {
+                let mut traces: Vec<RayTrace> = ray_indices
+                    .iter()
+                    .map(|index: &usize| {
+                        self.new_ray_trace(*index, &self.rays[*index], ray_parameters)
+                    })
+                    .collect();
+                traces.iter_mut().for_each(|trace: &mut RayTrace| {
+                    trace.trace(sphere_vector, ray_parameters);
+                });
+                self.count_rays_traced(&traces);
+
+                let mut accumulator: FrameAccumulator = FrameAccumulator::new(width, height);
+                accumulator.add_traces(&traces, ray_parameters);
+                accumulator
+            })
+            .collect();
+    }
+
+    pub fn finish_tiled_frame(
+        &mut self,
+        frame: Frame,
+        render_time_ms: f64,
+        ray_parameters: &RayParameters,
+        observer_parameters: &ObserverParameters,
+    ) -> Frame {
+        self.last_render_time_ms = render_time_ms;
+
+        return self.finish_frame(frame, ray_parameters, observer_parameters);
+    }
+
     fn limit_angle(current: f64, min_angle: f64, max_angle: f64, loop_angle: bool) -> f64 {
         if current < min_angle {
             if loop_angle {
@@ -133,12 +664,7 @@ impl Observer {
         };
     }
 
-    pub fn turn_hor(
-        &mut self,
-        angle: f64,
-        observer_parameters: &ObserverParameters,
-        ray_parameters: &RayParameters,
-    ) {
+    fn turn_hor(&mut self, angle: f64, observer_parameters: &ObserverParameters) {
         self.hor_angle += self.apply_slow_mode(angle, observer_parameters);
 
         self.hor_angle = Self::limit_angle(
@@ -147,16 +673,9 @@ impl Observer {
             observer_parameters.max_hor_angle,
             observer_parameters.hor_angle_loop,
         );
-
-        self.generate_rays(ray_parameters, observer_parameters);
     }
 
-    pub fn turn_ver(
-        &mut self,
-        angle: f64,
-        observer_parameters: &ObserverParameters,
-        ray_parameters: &RayParameters,
-    ) {
+    fn turn_ver(&mut self, angle: f64, observer_parameters: &ObserverParameters) {
         self.ver_angle += self.apply_slow_mode(angle, observer_parameters);
 
         self.ver_angle = Self::limit_angle(
@@ -165,68 +684,314 @@ impl Observer {
             observer_parameters.max_ver_angle,
             observer_parameters.ver_angle_loop,
         );
+    }
 
-        self.generate_rays(ray_parameters, observer_parameters);
+    fn turn_roll(&mut self, angle: f64, observer_parameters: &ObserverParameters) {
+        self.roll_angle += self.apply_slow_mode(angle, observer_parameters);
+
+        self.roll_angle = Self::limit_angle(
+            self.roll_angle,
+            -std::f64::consts::PI,
+            std::f64::consts::PI,
+            true,
+        );
     }
 
-    pub fn move_forward(
+    pub fn change_focus(
         &mut self,
-        dist: f64,
+        factor: f64,
         observer_parameters: &ObserverParameters,
         ray_parameters: &RayParameters,
     ) {
-        self.move_(
-            Speed {
-                x: self.hor_angle.cos() * self.ver_angle.cos() * dist,
-                y: self.hor_angle.sin() * dist,
-                z: self.ver_angle.sin() * dist,
-            },
-            observer_parameters,
-        );
+        self.focus_distance *= factor;
 
         self.generate_rays(ray_parameters, observer_parameters);
     }
 
-    pub fn move_hor(
+    // Held keys turn the observer and accelerate it, in units per second, the damping slows it down when they are released
+    pub fn update_movement(
         &mut self,
-        dist: f64,
+        input: &InputState,
+        dt: f64,
         observer_parameters: &ObserverParameters,
         ray_parameters: &RayParameters,
     ) {
-        self.move_(
-            Speed {
-                x: -self.hor_angle.sin() * dist,
-                y: self.hor_angle.cos() * dist,
-                z: 0.,
-            },
+        self.slow_speed_mode = input.slow;
+        let start_pos: Position = self.body.pos;
+        let start_angles: (f64, f64, f64) = (self.hor_angle, self.ver_angle, self.roll_angle);
+
+        self.turn_hor(
+            input.turn_right * observer_parameters.turn_speed * dt,
+            observer_parameters,
+        );
+        self.turn_ver(
+            input.turn_down * observer_parameters.turn_speed * dt,
+            observer_parameters,
+        );
+        self.turn_roll(
+            input.roll_right * observer_parameters.roll_speed * dt,
             observer_parameters,
         );
 
-        self.generate_rays(ray_parameters, observer_parameters);
+        // Walking stays on the ground whatever the vertical angle, moving up is a jump
+        let up: Position = observer_parameters.up_vector.normalized();
+        let right: Position = Position {
+            x: -self.hor_angle.sin(),
+            y: self.hor_angle.cos(),
+            z: 0.,
+        };
+        let (forward, vertical): (Position, f64) = match self.movement_mode {
+            MovementMode::Fly => (self.get_forward(), input.up),
+            MovementMode::Walk => (
+                Position {
+                    x: self.hor_angle.cos(),
+                    y: self.hor_angle.sin(),
+                    z: 0.,
+                },
+                0.,
+            ),
+        };
+
+        let mut direction: Position =
+            forward.scaled(input.forward) + right.scaled(input.right) + up.scaled(vertical);
+        // Diagonals are not faster
+        if direction.length() > 1. {
+            direction = direction.normalized();
+        }
+
+        // Exact solution of the damped motion over dt, the same path at any frame rate
+        let damping: f64 = observer_parameters.move_damping.max(1e-9);
+        let decay: f64 = (-damping * dt).exp();
+        let terminal_velocity: Position =
+            direction.scaled(observer_parameters.move_acceleration * self.speed_factor / damping);
+        let mut displacement: Position = terminal_velocity.scaled(dt)
+            + (self.velocity - terminal_velocity).scaled((1. - decay) / damping);
+        self.velocity = terminal_velocity + (self.velocity - terminal_velocity).scaled(decay);
+
+        let max_speed: f64 = self.apply_slow_mode(
+            observer_parameters.max_move_speed * self.speed_factor,
+            observer_parameters,
+        );
+        let speed: f64 = self.velocity.length();
+        if speed > max_speed {
+            self.velocity = self.velocity.scaled(max_speed / speed);
+            displacement = displacement.scaled((max_speed * dt / displacement.length()).min(1.));
+        } else if speed < 0.01 && direction.length() == 0. {
+            // Stopped for good, so that the accumulation can start
+            self.velocity = Position {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            };
+        }
+        self.body.pos = self.body.pos + displacement;
+
+        if matches!(self.movement_mode, MovementMode::Walk) && input.up > 0. && self.grounded {
+            self.body.speed = Self::position_to_speed(&up.scaled(observer_parameters.jump_speed));
+            self.grounded = false;
+        }
+
+        if self.body.pos.dist(&start_pos) > 0.
+            || start_angles != (self.hor_angle, self.ver_angle, self.roll_angle)
+        {
+            self.generate_rays(ray_parameters, observer_parameters);
+        }
     }
 
-    pub fn move_ver(
+    // Mouse motion in pixels, turning by mouse_sensitivity radians per pixel
+    pub fn look(
         &mut self,
-        dist: f64,
+        dx: f64,
+        dy: f64,
         observer_parameters: &ObserverParameters,
         ray_parameters: &RayParameters,
     ) {
-        self.move_(
-            Speed {
-                x: 0.,
-                y: 0.,
-                z: dist,
-            },
+        let invert: f64 = if observer_parameters.invert_mouse_y {
+            -1.
+        } else {
+            1.
+        };
+        self.turn_hor(
+            dx * observer_parameters.mouse_sensitivity,
+            observer_parameters,
+        );
+        self.turn_ver(
+            dy * observer_parameters.mouse_sensitivity * invert,
             observer_parameters,
         );
 
         self.generate_rays(ray_parameters, observer_parameters);
     }
 
-    fn move_(&mut self, speed: Speed, observer_parameters: &ObserverParameters) {
-        self.body.pos.x += self.apply_slow_mode(speed.x, observer_parameters);
-        self.body.pos.y += self.apply_slow_mode(speed.y, observer_parameters);
-        self.body.pos.z += self.apply_slow_mode(speed.z, observer_parameters);
+    // Mouse wheel steps, up makes the observer faster or zooms in
+    pub fn scroll(
+        &mut self,
+        steps: f64,
+        observer_parameters: &mut ObserverParameters,
+        ray_parameters: &RayParameters,
+    ) {
+        match observer_parameters.scroll_mode {
+            ScrollMode::Speed => {
+                self.speed_factor = (self.speed_factor
+                    * observer_parameters.scroll_speed_factor.powf(steps))
+                .clamp(0.01, 100.);
+                println!("Speed: x{:.2}", self.speed_factor);
+            }
+            ScrollMode::Fov => {
+                // The field of view of the current projection, the orthographic one is a height
+                let zoom: f64 = (1. - observer_parameters.scroll_fov_step).powf(steps);
+                match observer_parameters.projection {
+                    ProjectionMode::Perspective => {
+                        observer_parameters.vertical_fov =
+                            (observer_parameters.vertical_fov * zoom).clamp(0.01, 3.);
+                    }
+                    ProjectionMode::Orthographic => {
+                        observer_parameters.orthographic_height *= zoom;
+                    }
+                    ProjectionMode::Fisheye => {
+                        observer_parameters.fisheye_fov = (observer_parameters.fisheye_fov * zoom)
+                            .clamp(0.01, 2. * std::f64::consts::PI);
+                    }
+                    // Always the whole sphere
+                    ProjectionMode::Equirectangular => {}
+                }
+
+                self.generate_rays(ray_parameters, observer_parameters);
+            }
+        }
+    }
+
+    fn speed_to_position(speed: &Speed) -> Position {
+        return Position {
+            x: speed.x,
+            y: speed.y,
+            z: speed.z,
+        };
+    }
+
+    fn position_to_speed(position: &Position) -> Speed {
+        return Speed {
+            x: position.x,
+            y: position.y,
+            z: position.z,
+        };
+    }
+
+    // Falls in walk mode, then gets pushed out of the spheres and back inside the physics box
+    pub fn update_body(
+        &mut self,
+        dt: f64,
+        sphere_vector: &Vec<Sphere>,
+        observer_parameters: &ObserverParameters,
+        physics_parameters: &PhysicsParameters,
+        ray_parameters: &RayParameters,
+    ) {
+        let walking: bool = matches!(self.movement_mode, MovementMode::Walk);
+        if !walking && !observer_parameters.collisions {
+            return;
+        }
+
+        let start: Position = self.body.pos;
+        let up: Position = observer_parameters.up_vector.normalized();
+        let mut speed: Position = Self::speed_to_position(&self.body.speed);
+
+        if walking {
+            speed = speed - up.scaled(observer_parameters.walk_gravity * dt);
+            self.body.pos = self.body.pos + speed.scaled(dt);
+        }
+
+        self.grounded = false;
+        for _ in 0..COLLISION_ITERATIONS {
+            for s in sphere_vector.iter() {
+                let delta: Position = self.body.pos - s.pos;
+                let distance: f64 = delta.length();
+                let penetration: f64 = s.radius + self.body.radius - distance;
+                if penetration > 0. && distance > 0. {
+                    self.push_out(&delta.scaled(1. / distance), penetration, &mut speed, &up);
+                }
+            }
+
+            let inside: Position = Position {
+                x: self.body.pos.x.clamp(
+                    physics_parameters.min_x + self.body.radius,
+                    (physics_parameters.max_x - self.body.radius)
+                        .max(physics_parameters.min_x + self.body.radius),
+                ),
+                y: self.body.pos.y.clamp(
+                    physics_parameters.min_y + self.body.radius,
+                    (physics_parameters.max_y - self.body.radius)
+                        .max(physics_parameters.min_y + self.body.radius),
+                ),
+                z: self.body.pos.z.clamp(
+                    physics_parameters.min_z + self.body.radius,
+                    (physics_parameters.max_z - self.body.radius)
+                        .max(physics_parameters.min_z + self.body.radius),
+                ),
+            };
+            let push: Position = inside - self.body.pos;
+            let push_length: f64 = push.length();
+            if push_length > 0. {
+                self.push_out(&push.scaled(1. / push_length), push_length, &mut speed, &up);
+            }
+        }
+
+        if walking {
+            self.body.speed = Self::position_to_speed(&speed);
+        }
+
+        if self.body.pos.dist(&start) > 1e-9 {
+            self.generate_rays(ray_parameters, observer_parameters);
+        }
+    }
+
+    // Out of an obstacle along its normal, the speed into it is lost and standing on it is being on the ground
+    fn push_out(&mut self, normal: &Position, distance: f64, speed: &mut Position, up: &Position) {
+        self.body.pos = self.body.pos + normal.scaled(distance);
+
+        let speed_into: f64 = speed.dot(normal);
+        if speed_into < 0. {
+            *speed = *speed - normal.scaled(speed_into);
+        }
+        let velocity_into: f64 = self.velocity.dot(normal);
+        if velocity_into < 0. {
+            self.velocity = self.velocity - normal.scaled(velocity_into);
+        }
+
+        if normal.dot(up) > 0.7 {
+            self.grounded = true;
+        }
+    }
+
+    pub fn switch_movement_mode(&mut self) {
+        self.movement_mode = match self.movement_mode {
+            MovementMode::Fly => MovementMode::Walk,
+            MovementMode::Walk => MovementMode::Fly,
+        };
+        self.body.speed = Speed {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        };
+        self.velocity = Position {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        };
+        self.grounded = false;
+        println!("Movement mode: {}", self.movement_mode.to_string());
+    }
+
+    pub fn set_pose(
+        &mut self,
+        pose: &Keyframe,
+        observer_parameters: &ObserverParameters,
+        ray_parameters: &RayParameters,
+    ) {
+        self.body.pos = pose.pos;
+        self.hor_angle = pose.hor_angle;
+        self.ver_angle = pose.ver_angle;
+
+        self.generate_rays(ray_parameters, observer_parameters);
     }
 
     pub fn reset_position(
@@ -235,28 +1000,29 @@ impl Observer {
         ray_parameters: &RayParameters,
     ) {
         self.body.pos = observer_parameters.default_body.pos.clone();
+        self.body.speed = observer_parameters.default_body.speed.clone();
         self.body.is_visible = observer_parameters.default_body.is_visible;
+        self.roll_angle = 0.;
 
         self.generate_rays(ray_parameters, observer_parameters);
     }
 
     pub fn switch_accumulation_mode(&mut self) {
+        // Switched by hand, it's not left to the idle detection anymore
+        self.idle_accumulation = false;
         self.accumulation_mode = !self.accumulation_mode;
 
         if !self.accumulation_mode {
-            self.frame_stack = Vec::new();
+            self.history.clear();
         }
     }
 
-    pub fn switch_visibility(&mut self) {
-        self.body.is_visible = !self.body.is_visible;
-    }
-
-    pub fn slow_speed_mode(&mut self) {
-        self.slow_speed_mode = true;
+    pub fn next_render_pass(&mut self) {
+        self.render_pass = self.render_pass.next();
+        println!("Render pass: {}", self.render_pass.to_string());
     }
 
-    pub fn normal_speed_mode(&mut self) {
-        self.slow_speed_mode = false;
+    pub fn switch_visibility(&mut self) {
+        self.body.is_visible = !self.body.is_visible;
     }
 }
@@ -0,0 +1,181 @@
+use crate::position::Position;
+
+// Below this size the bodies are not split anymore, they can be at the same position
+const MAX_DEPTH: u64 = 32;
+
+struct Node {
+    center: Position,
+    half_size: f64,
+    mass: f64,
+    // Sum of the positions weighted by the masses, the center of mass once divided by the mass
+    weighted_position: Position,
+    body: Option<usize>,
+    children: Option<[usize; 8]>,
+    depth: u64,
+}
+
+impl Node {
+    fn new(center: Position, half_size: f64, depth: u64) -> Node {
+        return Node {
+            center: center,
+            half_size: half_size,
+            mass: 0.,
+            weighted_position: Position {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+            body: Option::None,
+            children: Option::None,
+            depth: depth,
+        };
+    }
+
+    fn get_center_of_mass(&self) -> Position {
+        return self.weighted_position.scaled(1. / self.mass);
+    }
+
+    fn get_octant(&self, position: &Position) -> usize {
+        return (if position.x >= self.center.x { 1 } else { 0 })
+            + (if position.y >= self.center.y { 2 } else { 0 })
+            + (if position.z >= self.center.z { 4 } else { 0 });
+    }
+}
+
+// Barnes-Hut tree: far away groups of bodies attract as a single body at their center of mass
+pub struct Octree {
+    nodes: Vec<Node>,
+}
+
+impl Octree {
+    pub fn new(positions: &Vec<Position>, masses: &Vec<f64>) -> Octree {
+        // Smallest cube around all the bodies
+        let mut min: Position = Position {
+            x: f64::MAX,
+            y: f64::MAX,
+            z: f64::MAX,
+        };
+        let mut max: Position = -min;
+        for position in positions.iter() {
+            min = Position {
+                x: min.x.min(position.x),
+                y: min.y.min(position.y),
+                z: min.z.min(position.z),
+            };
+            max = Position {
+                x: max.x.max(position.x),
+                y: max.y.max(position.y),
+                z: max.z.max(position.z),
+            };
+        }
+        let half_size: f64 = ((max.x - min.x).max(max.y - min.y).max(max.z - min.z) / 2.).max(1e-9);
+
+        let mut octree: Octree = Octree {
+            nodes: vec![Node::new((min + max).scaled(0.5), half_size, 0)],
+        };
+
+        for index in 0..positions.len() {
+            if masses[index] > 0. {
+                octree.insert(0, index, positions, masses);
+            }
+        }
+
+        return octree;
+    }
+
+    fn insert(&mut self, node: usize, index: usize, positions: &Vec<Position>, masses: &Vec<f64>) {
+        let position: Position = positions[index];
+        let was_empty: bool = self.nodes[node].mass <= 0.;
+        self.nodes[node].mass += masses[index];
+        self.nodes[node].weighted_position =
+            self.nodes[node].weighted_position + position.scaled(masses[index]);
+
+        if self.nodes[node].children.is_none() {
+            if was_empty {
+                self.nodes[node].body = Option::Some(index);
+                return;
+            }
+            if self.nodes[node].depth >= MAX_DEPTH {
+                return;
+            }
+
+            // A leaf with a body already, both go down one level
+            self.subdivide(node);
+            if let Some(other) = self.nodes[node].body.take() {
+                let child: usize = self.get_child(node, &positions[other]);
+                self.insert(child, other, positions, masses);
+            }
+        }
+
+        let child: usize = self.get_child(node, &position);
+        self.insert(child, index, positions, masses);
+    }
+
+    fn subdivide(&mut self, node: usize) {
+        let center: Position = self.nodes[node].center;
+        let quarter: f64 = self.nodes[node].half_size / 2.;
+        let depth: u64 = self.nodes[node].depth + 1;
+
+        let mut children: [usize; 8] = [0; 8];
+        for (octant, child) in children.iter_mut().enumerate() {
+            let offset: Position = Position {
+                x: if octant & 1 > 0 { quarter } else { -quarter },
+                y: if octant & 2 > 0 { quarter } else { -quarter },
+                z: if octant & 4 > 0 { quarter } else { -quarter },
+            };
+            *child = self.nodes.len();
+            self.nodes.push(Node::new(center + offset, quarter, depth));
+        }
+        self.nodes[node].children = Option::Some(children);
+    }
+
+    fn get_child(&self, node: usize, position: &Position) -> usize {
+        return self.nodes[node].children.unwrap()[self.nodes[node].get_octant(position)];
+    }
+
+    // Gravitational acceleration at a position, a node is opened when its size seen from the position is above theta
+    pub fn get_acceleration(
+        &self,
+        position: &Position,
+        gravitational_constant: f64,
+        softening: f64,
+        theta: f64,
+    ) -> Position {
+        let mut acceleration: Position = Position {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        };
+        let mut stack: Vec<usize> = vec![0];
+
+        while let Some(node) = stack.pop() {
+            let node: &Node = &self.nodes[node];
+            if node.mass <= 0. {
+                continue;
+            }
+
+            let delta: Position = node.get_center_of_mass() - *position;
+            let distance_squared: f64 = delta.dot(&delta);
+
+            match node.children {
+                Some(children)
+                    if 4. * node.half_size * node.half_size >= theta * theta * distance_squared =>
+                {
+                    stack.extend(children.iter());
+                }
+                _ => {
+                    // The body itself is at a null distance and doesn't pull
+                    acceleration = acceleration
+                        + delta.scaled(
+                            gravitational_constant * node.mass
+                                / (distance_squared + softening * softening)
+                                    .powf(1.5)
+                                    .max(1e-12),
+                        );
+                }
+            }
+        }
+
+        return acceleration;
+    }
+}
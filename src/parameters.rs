@@ -4,14 +4,26 @@ use serde_json::Value;
 use std::env;
 use std::fs;
 
-use crate::observer::Observer;
+use crate::camera::ProjectionMode;
+use crate::camera_path::{CameraPath, Keyframe, PathInterpolation};
+use crate::denoise;
+use crate::environment::{self, EnvironmentType};
+use crate::filter::ReconstructionFilter;
+use crate::key_bindings::KeyBindings;
+use crate::observer::{MovementMode, Observer, ScrollMode};
+use crate::physics::Integrator;
+use crate::render_pass::RenderPass;
+use crate::stereo::StereoMode;
+use crate::texture::Texture;
+use crate::tile::TileOrder;
 use crate::{
+    normal_map::{NormalMap, NormalMapType},
     position::Position,
     speed::Speed,
     sphere::{Sphere, SphereType},
 };
 
-fn get_parameter_file_path() -> String {
+pub fn get_parameter_file_path() -> String {
     return env::current_dir()
         .expect("Current path is invalid")
         .to_str()
@@ -49,6 +61,72 @@ fn type_from_json(data: &Value, default: &SphereType) -> SphereType {
     return SphereType::from_string(data.as_str().get_or_insert(default.to_string()));
 }
 
+fn strings_from_json(data: &Value, default: &Vec<String>) -> Vec<String> {
+    return match data.as_array() {
+        Some(value_array) => value_array
+            .iter()
+            .filter_map(|value: &Value| value.as_str())
+            .map(|value: &str| value.to_owned())
+            .collect(),
+        None => default.clone(),
+    };
+}
+
+fn normal_map_type_from_json(data: &Value, default: &NormalMapType) -> NormalMapType {
+    return NormalMapType::from_string(data.as_str().get_or_insert(default.to_string()));
+}
+
+fn normal_map_from_json(data: &Value, default: &NormalMap) -> NormalMap {
+    return NormalMap {
+        type_: normal_map_type_from_json(&data["type_"], &default.type_),
+        strength: *data["strength"].as_f64().get_or_insert(default.strength),
+        scale: *data["scale"].as_f64().get_or_insert(default.scale),
+        octaves: *data["octaves"].as_u64().get_or_insert(default.octaves),
+        texture_index: *data["texture_index"]
+            .as_u64()
+            .get_or_insert(default.texture_index),
+    };
+}
+
+fn keyframe_from_json(data: &Value) -> Keyframe {
+    return Keyframe {
+        time: *data["time"].as_f64().get_or_insert(0.),
+        pos: position_from_json(
+            &data["pos"],
+            &Position {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            },
+        ),
+        hor_angle: *data["hor_angle"].as_f64().get_or_insert(0.),
+        ver_angle: *data["ver_angle"].as_f64().get_or_insert(0.),
+    };
+}
+
+fn camera_path_from_json(data: &Value, default: &CameraPath) -> CameraPath {
+    return CameraPath {
+        keyframes: match data["keyframes"].as_array() {
+            Some(array) => {
+                // Interpolation expects the keyframes in time order, an edited file may not be
+                let mut keyframes: Vec<Keyframe> = array.iter().map(keyframe_from_json).collect();
+                keyframes
+                    .sort_by(|a: &Keyframe, b: &Keyframe| a.time.partial_cmp(&b.time).unwrap());
+                keyframes
+            }
+            None => default.keyframes.clone(),
+        },
+        interpolation: PathInterpolation::from_string(
+            data["interpolation"]
+                .as_str()
+                .get_or_insert(default.interpolation.to_string()),
+        ),
+        keyframe_interval: *data["keyframe_interval"]
+            .as_f64()
+            .get_or_insert(default.keyframe_interval),
+    };
+}
+
 fn sphere_from_json(data: &Value, default: &Sphere) -> Sphere {
     return Sphere {
         pos: position_from_json(&data["pos"], &default.pos),
@@ -65,9 +143,11 @@ fn sphere_from_json(data: &Value, default: &Sphere) -> Sphere {
         refractivity_index: *data["refractivity_index"]
             .as_f64()
             .get_or_insert(default.refractivity_index),
+        normal_map: normal_map_from_json(&data["normal_map"], &default.normal_map),
         is_visible: *data["is_visible"]
             .as_bool()
             .get_or_insert(default.is_visible),
+        density: *data["density"].as_f64().get_or_insert(default.density),
     };
 }
 
@@ -79,6 +159,8 @@ pub enum SphereGenerationMode {
     Hardcoded,
     Random,
     InLine,
+    SolarSystem,
+    Galaxy,
 }
 
 impl SphereGenerationMode {
@@ -87,6 +169,8 @@ impl SphereGenerationMode {
             Self::Hardcoded => "Hardcoded",
             Self::Random => "Random",
             Self::InLine => "InLine",
+            Self::SolarSystem => "SolarSystem",
+            Self::Galaxy => "Galaxy",
         };
     }
 
@@ -95,29 +179,63 @@ impl SphereGenerationMode {
             "Reflexive" => Self::Hardcoded,
             "Random" => Self::Random,
             "InLine" => Self::InLine,
+            "SolarSystem" => Self::SolarSystem,
+            "Galaxy" => Self::Galaxy,
             _ => Self::Hardcoded,
         };
     }
 }
 
 pub struct ObserverParameters {
-    pub look_vector_distance: f64,
-    pub look_up_angle: f64,
-    pub look_down_angle: f64,
-    pub look_left_angle: f64,
-    pub look_right_angle: f64,
+    pub projection: ProjectionMode,
+    pub vertical_fov: f64,
+    pub orthographic_height: f64,
+    pub fisheye_fov: f64,
+    pub aspect_ratio: f64,
+    pub up_vector: Position,
+    pub aperture_radius: f64,
+    pub focus_distance: f64,
+    pub focus_step_factor: f64,
+    pub autofocus: bool,
+    pub shutter_interval: f64,
+    pub stereo_mode: StereoMode,
+    pub interpupillary_distance: f64,
+    pub default_render_pass: RenderPass,
+    pub temporal_reprojection: bool,
+    pub max_history_frames: u64,
+    // A reprojected pixel weighs at most this many new frames, the reflections change with the view
+    pub max_reprojected_frames: u64,
+    pub reprojection_depth_tolerance: f64,
+    pub adaptive_resolution: bool,
+    pub target_frame_time_ms: f64,
+    pub min_render_scale: f64,
+    pub accumulate_when_idle: bool,
+    pub idle_frames_before_accumulation: u64,
+    pub adaptive_sampling: bool,
+    pub adaptive_error_threshold: f64,
+    pub adaptive_min_samples: u64,
+    pub adaptive_max_samples_per_frame: u64,
+    pub movement_mode: MovementMode,
+    pub collisions: bool,
+    pub walk_gravity: f64,
+    pub jump_speed: f64,
+    pub mouse_look: bool,
+    pub mouse_sensitivity: f64,
+    pub invert_mouse_y: bool,
+    pub scroll_mode: ScrollMode,
+    pub scroll_speed_factor: f64,
+    pub scroll_fov_step: f64,
+    pub turn_speed: f64,
+    pub roll_speed: f64,
     pub min_hor_angle: f64,
     pub max_hor_angle: f64,
     pub hor_angle_loop: bool,
     pub min_ver_angle: f64,
     pub max_ver_angle: f64,
     pub ver_angle_loop: bool,
-    pub move_forward_distance: f64,
-    pub move_backward_distance: f64,
-    pub move_right_distance: f64,
-    pub move_left_distance: f64,
-    pub move_up_distance: f64,
-    pub move_down_distance: f64,
+    pub move_acceleration: f64,
+    pub max_move_speed: f64,
+    pub move_damping: f64,
     pub slow_mode_factor: f64,
     pub default_body: Sphere,
 }
@@ -125,21 +243,129 @@ pub struct ObserverParameters {
 impl ObserverParameters {
     fn get_from_json(data: &Value, default: &Self) -> Self {
         return ObserverParameters {
-            look_vector_distance: *data["look_vector_distance"]
+            projection: ProjectionMode::from_string(
+                data["projection"]
+                    .as_str()
+                    .get_or_insert(default.projection.to_string()),
+            ),
+            vertical_fov: *data["vertical_fov"]
+                .as_f64()
+                .get_or_insert(default.vertical_fov),
+            orthographic_height: *data["orthographic_height"]
+                .as_f64()
+                .get_or_insert(default.orthographic_height),
+            fisheye_fov: *data["fisheye_fov"]
+                .as_f64()
+                .get_or_insert(default.fisheye_fov),
+            aspect_ratio: *data["aspect_ratio"]
+                .as_f64()
+                .get_or_insert(default.aspect_ratio),
+            up_vector: position_from_json(&data["up_vector"], &default.up_vector),
+            aperture_radius: *data["aperture_radius"]
+                .as_f64()
+                .get_or_insert(default.aperture_radius),
+            focus_distance: *data["focus_distance"]
+                .as_f64()
+                .get_or_insert(default.focus_distance),
+            focus_step_factor: *data["focus_step_factor"]
+                .as_f64()
+                .get_or_insert(default.focus_step_factor),
+            autofocus: *data["autofocus"].as_bool().get_or_insert(default.autofocus),
+            shutter_interval: *data["shutter_interval"]
+                .as_f64()
+                .get_or_insert(default.shutter_interval),
+            stereo_mode: StereoMode::from_string(
+                data["stereo_mode"]
+                    .as_str()
+                    .get_or_insert(default.stereo_mode.to_string()),
+            ),
+            interpupillary_distance: *data["interpupillary_distance"]
+                .as_f64()
+                .get_or_insert(default.interpupillary_distance),
+            default_render_pass: RenderPass::from_string(
+                data["default_render_pass"]
+                    .as_str()
+                    .get_or_insert(default.default_render_pass.to_string()),
+            ),
+            temporal_reprojection: *data["temporal_reprojection"]
+                .as_bool()
+                .get_or_insert(default.temporal_reprojection),
+            max_history_frames: *data["max_history_frames"]
+                .as_u64()
+                .get_or_insert(default.max_history_frames),
+            max_reprojected_frames: *data["max_reprojected_frames"]
+                .as_u64()
+                .get_or_insert(default.max_reprojected_frames),
+            reprojection_depth_tolerance: *data["reprojection_depth_tolerance"]
+                .as_f64()
+                .get_or_insert(default.reprojection_depth_tolerance),
+            adaptive_resolution: *data["adaptive_resolution"]
+                .as_bool()
+                .get_or_insert(default.adaptive_resolution),
+            target_frame_time_ms: *data["target_frame_time_ms"]
+                .as_f64()
+                .get_or_insert(default.target_frame_time_ms),
+            min_render_scale: *data["min_render_scale"]
                 .as_f64()
-                .get_or_insert(default.look_vector_distance),
-            look_up_angle: *data["look_up_angle"]
+                .get_or_insert(default.min_render_scale),
+            accumulate_when_idle: *data["accumulate_when_idle"]
+                .as_bool()
+                .get_or_insert(default.accumulate_when_idle),
+            idle_frames_before_accumulation: *data["idle_frames_before_accumulation"]
+                .as_u64()
+                .get_or_insert(default.idle_frames_before_accumulation),
+            adaptive_sampling: *data["adaptive_sampling"]
+                .as_bool()
+                .get_or_insert(default.adaptive_sampling),
+            adaptive_error_threshold: *data["adaptive_error_threshold"]
                 .as_f64()
-                .get_or_insert(default.look_up_angle),
-            look_down_angle: *data["look_down_angle"]
+                .get_or_insert(default.adaptive_error_threshold),
+            adaptive_min_samples: *data["adaptive_min_samples"]
+                .as_u64()
+                .get_or_insert(default.adaptive_min_samples),
+            adaptive_max_samples_per_frame: *data["adaptive_max_samples_per_frame"]
+                .as_u64()
+                .get_or_insert(default.adaptive_max_samples_per_frame),
+            movement_mode: MovementMode::from_string(
+                data["movement_mode"]
+                    .as_str()
+                    .get_or_insert(default.movement_mode.to_string()),
+            ),
+            collisions: *data["collisions"]
+                .as_bool()
+                .get_or_insert(default.collisions),
+            walk_gravity: *data["walk_gravity"]
                 .as_f64()
-                .get_or_insert(default.look_down_angle),
-            look_left_angle: *data["look_left_angle"]
+                .get_or_insert(default.walk_gravity),
+            jump_speed: *data["jump_speed"]
                 .as_f64()
-                .get_or_insert(default.look_left_angle),
-            look_right_angle: *data["look_right_angle"]
+                .get_or_insert(default.jump_speed),
+            mouse_look: *data["mouse_look"]
+                .as_bool()
+                .get_or_insert(default.mouse_look),
+            mouse_sensitivity: *data["mouse_sensitivity"]
                 .as_f64()
-                .get_or_insert(default.look_right_angle),
+                .get_or_insert(default.mouse_sensitivity),
+            invert_mouse_y: *data["invert_mouse_y"]
+                .as_bool()
+                .get_or_insert(default.invert_mouse_y),
+            scroll_mode: ScrollMode::from_string(
+                data["scroll_mode"]
+                    .as_str()
+                    .get_or_insert(default.scroll_mode.to_string()),
+            ),
+            scroll_speed_factor: *data["scroll_speed_factor"]
+                .as_f64()
+                .get_or_insert(default.scroll_speed_factor),
+            scroll_fov_step: *data["scroll_fov_step"]
+                .as_f64()
+                .get_or_insert(default.scroll_fov_step),
+            turn_speed: *data["turn_speed"]
+                .as_f64()
+                .get_or_insert(default.turn_speed),
+            roll_speed: *data["roll_speed"]
+                .as_f64()
+                .get_or_insert(default.roll_speed),
             min_hor_angle: *data["min_hor_angle"]
                 .as_f64()
                 .get_or_insert(default.min_hor_angle),
@@ -158,24 +384,15 @@ impl ObserverParameters {
             ver_angle_loop: *data["ver_angle_loop"]
                 .as_bool()
                 .get_or_insert(default.ver_angle_loop),
-            move_forward_distance: *data["move_forward_distance"]
-                .as_f64()
-                .get_or_insert(default.move_forward_distance),
-            move_backward_distance: *data["move_backward_distance"]
-                .as_f64()
-                .get_or_insert(default.move_backward_distance),
-            move_right_distance: *data["move_right_distance"]
-                .as_f64()
-                .get_or_insert(default.move_right_distance),
-            move_left_distance: *data["move_left_distance"]
+            move_acceleration: *data["move_acceleration"]
                 .as_f64()
-                .get_or_insert(default.move_left_distance),
-            move_up_distance: *data["move_up_distance"]
+                .get_or_insert(default.move_acceleration),
+            max_move_speed: *data["max_move_speed"]
                 .as_f64()
-                .get_or_insert(default.move_up_distance),
-            move_down_distance: *data["move_down_distance"]
+                .get_or_insert(default.max_move_speed),
+            move_damping: *data["move_damping"]
                 .as_f64()
-                .get_or_insert(default.move_down_distance),
+                .get_or_insert(default.move_damping),
             slow_mode_factor: *data["slow_mode_factor"]
                 .as_f64()
                 .get_or_insert(default.slow_mode_factor),
@@ -184,6 +401,79 @@ impl ObserverParameters {
     }
 }
 
+pub struct EnvironmentParameters {
+    pub type_: EnvironmentType,
+    pub zenith_color: Color,
+    pub horizon_color: Color,
+    pub ground_color: Color,
+    pub sun_direction: Position,
+    pub sun_color: Color,
+    pub sun_size: f64,
+    pub sun_intensity: f64,
+    pub image_path: String,
+    pub image_rotation: f64,
+    pub image_exposure: f64,
+    pub sample_as_light: bool,
+    pub light_sample_factor: f64,
+    // Smoother surfaces keep only their bounce ray, a diffuse light sample is wrong for a glossy lobe
+    pub light_sample_max_smoothness: f64,
+    pub image: Option<Texture>,
+    pub image_cdf: Vec<f64>,
+}
+
+impl EnvironmentParameters {
+    fn get_from_json(data: &Value, default: &Self) -> Self {
+        let type_: EnvironmentType = EnvironmentType::from_string(
+            data["type_"]
+                .as_str()
+                .get_or_insert(default.type_.to_string()),
+        );
+        let image_path: String = data["image_path"]
+            .as_str()
+            .get_or_insert(&default.image_path)
+            .to_string();
+        let image: Option<Texture> = match type_ {
+            EnvironmentType::Image => Texture::load(&image_path),
+            _ => Option::None,
+        };
+
+        return EnvironmentParameters {
+            type_: type_,
+            zenith_color: color_from_json(&data["zenith_color"], &default.zenith_color),
+            horizon_color: color_from_json(&data["horizon_color"], &default.horizon_color),
+            ground_color: color_from_json(&data["ground_color"], &default.ground_color),
+            sun_direction: position_from_json(&data["sun_direction"], &default.sun_direction)
+                .normalized(),
+            sun_color: color_from_json(&data["sun_color"], &default.sun_color),
+            sun_size: *data["sun_size"].as_f64().get_or_insert(default.sun_size),
+            sun_intensity: *data["sun_intensity"]
+                .as_f64()
+                .get_or_insert(default.sun_intensity),
+            image_path: image_path,
+            image_rotation: *data["image_rotation"]
+                .as_f64()
+                .get_or_insert(default.image_rotation),
+            image_exposure: *data["image_exposure"]
+                .as_f64()
+                .get_or_insert(default.image_exposure),
+            sample_as_light: *data["sample_as_light"]
+                .as_bool()
+                .get_or_insert(default.sample_as_light),
+            light_sample_factor: *data["light_sample_factor"]
+                .as_f64()
+                .get_or_insert(default.light_sample_factor),
+            light_sample_max_smoothness: *data["light_sample_max_smoothness"]
+                .as_f64()
+                .get_or_insert(default.light_sample_max_smoothness),
+            image_cdf: match &image {
+                Some(texture) => environment::build_image_cdf(texture),
+                None => vec![],
+            },
+            image: image,
+        };
+    }
+}
+
 pub struct RayParameters {
     pub min_hor_value: i64,
     pub max_hor_value: i64,
@@ -199,10 +489,19 @@ pub struct RayParameters {
     pub min_random_bounce_angle_change: f64,
     pub max_random_bounce_angle_change: f64,
     pub reflect_inside_spheres: bool,
+    pub samples_per_pixel: u64,
+    pub reconstruction_filter: ReconstructionFilter,
+    pub filter_radius: f64,
+    pub normal_map_paths: Vec<String>,
+    pub normal_map_textures: Vec<Option<Texture>>,
+    pub environment: EnvironmentParameters,
 }
 
 impl RayParameters {
     fn get_from_json(data: &Value, default: &Self) -> Self {
+        let normal_map_paths: Vec<String> =
+            strings_from_json(&data["normal_map_paths"], &default.normal_map_paths);
+
         return RayParameters {
             min_hor_value: *data["min_hor_value"]
                 .as_i64()
@@ -244,6 +543,23 @@ impl RayParameters {
             reflect_inside_spheres: *data["reflect_inside_spheres"]
                 .as_bool()
                 .get_or_insert(default.reflect_inside_spheres),
+            samples_per_pixel: *data["samples_per_pixel"]
+                .as_u64()
+                .get_or_insert(default.samples_per_pixel),
+            reconstruction_filter: ReconstructionFilter::from_string(
+                data["reconstruction_filter"]
+                    .as_str()
+                    .get_or_insert(default.reconstruction_filter.to_string()),
+            ),
+            filter_radius: *data["filter_radius"]
+                .as_f64()
+                .get_or_insert(default.filter_radius),
+            normal_map_textures: Texture::load_vec(&normal_map_paths),
+            normal_map_paths: normal_map_paths,
+            environment: EnvironmentParameters::get_from_json(
+                &data["environment"],
+                &default.environment,
+            ),
         };
     }
 }
@@ -260,6 +576,8 @@ pub struct SphereParameters {
     pub max_smoothness: f64,
     pub min_refractivity_index: f64,
     pub max_refractivity_index: f64,
+    pub normal_map: NormalMap,
+    pub density: f64,
 }
 
 impl SphereParameters {
@@ -310,12 +628,18 @@ impl SphereParameters {
             max_refractivity_index: *data["max_refractivity_index"]
                 .as_f64()
                 .get_or_insert(default.max_refractivity_index),
+            normal_map: normal_map_from_json(&data["normal_map"], &default.normal_map),
+            density: *data["density"].as_f64().get_or_insert(default.density),
         };
     }
 }
 
 pub struct PhysicsParameters {
-    pub g: f64,
+    pub gravity: Position,
+    pub integrator: Integrator,
+    pub fixed_timestep: f64,
+    pub max_substeps: u64,
+    pub time_scale: f64,
     pub enabled: bool,
     pub min_x: f64,
     pub max_x: f64,
@@ -329,12 +653,35 @@ pub struct PhysicsParameters {
     pub max_vy: f64,
     pub min_vz: f64,
     pub max_vz: f64,
+    pub collisions: bool,
+    pub restitution: f64,
+    pub friction: f64,
+    pub grid_cell_size: f64,
+    pub n_body: bool,
+    pub gravitational_constant: f64,
+    pub softening: f64,
+    pub barnes_hut_theta: f64,
+    pub barnes_hut_min_count: u64,
 }
 
 impl PhysicsParameters {
     fn get_from_json(data: &Value, default: &Self) -> Self {
         return PhysicsParameters {
-            g: *data["g"].as_f64().get_or_insert(default.g),
+            gravity: position_from_json(&data["gravity"], &default.gravity),
+            integrator: Integrator::from_string(
+                data["integrator"]
+                    .as_str()
+                    .get_or_insert(default.integrator.to_string()),
+            ),
+            fixed_timestep: *data["fixed_timestep"]
+                .as_f64()
+                .get_or_insert(default.fixed_timestep),
+            max_substeps: *data["max_substeps"]
+                .as_u64()
+                .get_or_insert(default.max_substeps),
+            time_scale: *data["time_scale"]
+                .as_f64()
+                .get_or_insert(default.time_scale),
             enabled: *data["enabled"].as_bool().get_or_insert(default.enabled),
             min_x: *data["min_x"].as_f64().get_or_insert(default.min_x),
             max_x: *data["max_x"].as_f64().get_or_insert(default.max_x),
@@ -348,6 +695,104 @@ impl PhysicsParameters {
             max_vy: *data["max_vy"].as_f64().get_or_insert(default.max_vy),
             min_vz: *data["min_vz"].as_f64().get_or_insert(default.min_vz),
             max_vz: *data["max_vz"].as_f64().get_or_insert(default.max_vz),
+            collisions: *data["collisions"]
+                .as_bool()
+                .get_or_insert(default.collisions),
+            restitution: *data["restitution"]
+                .as_f64()
+                .get_or_insert(default.restitution),
+            friction: *data["friction"].as_f64().get_or_insert(default.friction),
+            grid_cell_size: *data["grid_cell_size"]
+                .as_f64()
+                .get_or_insert(default.grid_cell_size),
+            n_body: *data["n_body"].as_bool().get_or_insert(default.n_body),
+            gravitational_constant: *data["gravitational_constant"]
+                .as_f64()
+                .get_or_insert(default.gravitational_constant),
+            softening: *data["softening"].as_f64().get_or_insert(default.softening),
+            barnes_hut_theta: *data["barnes_hut_theta"]
+                .as_f64()
+                .get_or_insert(default.barnes_hut_theta),
+            barnes_hut_min_count: *data["barnes_hut_min_count"]
+                .as_u64()
+                .get_or_insert(default.barnes_hut_min_count),
+        };
+    }
+}
+
+pub struct DenoiserParameters {
+    pub enabled: bool,
+    pub iterations: u64,
+    pub sigma_color: f64,
+    pub sigma_normal: f64,
+    pub sigma_depth: f64,
+    pub sigma_albedo: f64,
+}
+
+impl DenoiserParameters {
+    fn get_from_json(data: &Value, default: &Self) -> Self {
+        return DenoiserParameters {
+            enabled: *data["enabled"].as_bool().get_or_insert(default.enabled),
+            iterations: (*data["iterations"]
+                .as_u64()
+                .get_or_insert(default.iterations))
+            .min(denoise::MAX_ITERATIONS),
+            sigma_color: *data["sigma_color"]
+                .as_f64()
+                .get_or_insert(default.sigma_color),
+            sigma_normal: *data["sigma_normal"]
+                .as_f64()
+                .get_or_insert(default.sigma_normal),
+            sigma_depth: *data["sigma_depth"]
+                .as_f64()
+                .get_or_insert(default.sigma_depth),
+            sigma_albedo: *data["sigma_albedo"]
+                .as_f64()
+                .get_or_insert(default.sigma_albedo),
+        };
+    }
+}
+
+pub struct TileParameters {
+    pub enabled: bool,
+    pub tile_size: i64,
+    pub order: TileOrder,
+    pub batch_time_ms: f64,
+}
+
+impl TileParameters {
+    fn get_from_json(data: &Value, default: &Self) -> Self {
+        return TileParameters {
+            enabled: *data["enabled"].as_bool().get_or_insert(default.enabled),
+            tile_size: *data["tile_size"].as_i64().get_or_insert(default.tile_size),
+            order: TileOrder::from_string(
+                data["order"]
+                    .as_str()
+                    .get_or_insert(default.order.to_string()),
+            ),
+            batch_time_ms: *data["batch_time_ms"]
+                .as_f64()
+                .get_or_insert(default.batch_time_ms),
+        };
+    }
+}
+
+pub struct HudParameters {
+    pub enabled: bool,
+    pub text_scale: i64,
+    pub update_interval_ms: f64,
+}
+
+impl HudParameters {
+    fn get_from_json(data: &Value, default: &Self) -> Self {
+        return HudParameters {
+            enabled: *data["enabled"].as_bool().get_or_insert(default.enabled),
+            text_scale: *data["text_scale"]
+                .as_i64()
+                .get_or_insert(default.text_scale),
+            update_interval_ms: *data["update_interval_ms"]
+                .as_f64()
+                .get_or_insert(default.update_interval_ms),
         };
     }
 }
@@ -359,39 +804,77 @@ pub struct Parameters {
     pub ray_parameters: RayParameters,
     pub sphere_parameters: Vec<SphereParameters>,
     pub physics_parameters: PhysicsParameters,
+    pub denoiser_parameters: DenoiserParameters,
+    pub tile_parameters: TileParameters,
+    pub hud_parameters: HudParameters,
+    pub camera_path: CameraPath,
+    pub key_bindings: KeyBindings,
 }
 
 impl Parameters {
     pub fn default() -> Parameters {
         let width: i64 = 128;
         let height: i64 = 128;
-        let look_angle = 0.1;
-        let move_distance = 0.5;
         let physics_bounds_value = 20.;
-        let speed_bounds_value = 0.0025;
+        let speed_bounds_value = 0.15;
         let random_bounce_angle_change = std::f64::consts::FRAC_PI_2;
 
         return Parameters {
             frame_period_ms: 0,
             display_scale: 5.,
             observer_parameters: ObserverParameters {
-                look_vector_distance: (height / 2) as f64,
-                look_up_angle: -look_angle,
-                look_down_angle: look_angle,
-                look_left_angle: -look_angle,
-                look_right_angle: look_angle,
+                projection: ProjectionMode::Perspective,
+                vertical_fov: std::f64::consts::FRAC_PI_2,
+                orthographic_height: 40.,
+                fisheye_fov: std::f64::consts::PI,
+                aspect_ratio: 0.,
+                up_vector: Position {
+                    x: 0.,
+                    y: 0.,
+                    z: -1.,
+                },
+                aperture_radius: 0.,
+                focus_distance: 10.,
+                focus_step_factor: 1.1,
+                autofocus: false,
+                shutter_interval: 0.,
+                stereo_mode: StereoMode::Off,
+                interpupillary_distance: 0.3,
+                default_render_pass: RenderPass::Color,
+                temporal_reprojection: true,
+                max_history_frames: 0,
+                max_reprojected_frames: 4,
+                reprojection_depth_tolerance: 0.05,
+                adaptive_resolution: false,
+                target_frame_time_ms: 33.,
+                min_render_scale: 0.25,
+                accumulate_when_idle: false,
+                idle_frames_before_accumulation: 2,
+                adaptive_sampling: false,
+                adaptive_error_threshold: 0.01,
+                adaptive_min_samples: 16,
+                adaptive_max_samples_per_frame: 4,
+                movement_mode: MovementMode::Fly,
+                collisions: false,
+                walk_gravity: 20.,
+                jump_speed: 8.,
+                mouse_look: true,
+                mouse_sensitivity: 0.003,
+                invert_mouse_y: false,
+                scroll_mode: ScrollMode::Speed,
+                scroll_speed_factor: 1.25,
+                scroll_fov_step: 0.05,
+                turn_speed: 1.5,
+                roll_speed: 1.5,
                 min_hor_angle: 0.,
                 max_hor_angle: 2. * std::f64::consts::PI,
                 hor_angle_loop: true,
                 min_ver_angle: -std::f64::consts::FRAC_PI_2,
                 max_ver_angle: std::f64::consts::FRAC_PI_2,
                 ver_angle_loop: false,
-                move_forward_distance: move_distance,
-                move_backward_distance: -move_distance,
-                move_right_distance: move_distance,
-                move_left_distance: -move_distance,
-                move_up_distance: -move_distance,
-                move_down_distance: move_distance,
+                move_acceleration: 60.,
+                max_move_speed: 10.,
+                move_damping: 6.,
                 slow_mode_factor: 0.05,
                 default_body: Sphere {
                     pos: Position {
@@ -415,7 +898,9 @@ impl Parameters {
                     type_: SphereType::Reflexive,
                     smoothness: 1.,
                     refractivity_index: 1.,
+                    normal_map: NormalMap::none(),
                     is_visible: true,
+                    density: 1.,
                 },
             },
             ray_parameters: RayParameters {
@@ -433,6 +918,34 @@ impl Parameters {
                 min_random_bounce_angle_change: -random_bounce_angle_change,
                 max_random_bounce_angle_change: random_bounce_angle_change,
                 reflect_inside_spheres: false,
+                samples_per_pixel: 1,
+                reconstruction_filter: ReconstructionFilter::Box,
+                filter_radius: 0.5,
+                normal_map_paths: vec![],
+                normal_map_textures: vec![],
+                environment: EnvironmentParameters {
+                    type_: EnvironmentType::Color,
+                    zenith_color: Color::RGB(40, 90, 200),
+                    horizon_color: Color::RGB(190, 210, 235),
+                    ground_color: Color::RGB(60, 55, 50),
+                    sun_direction: Position {
+                        x: 1.,
+                        y: 0.5,
+                        z: -1.,
+                    }
+                    .normalized(),
+                    sun_color: Color::RGB(255, 240, 210),
+                    sun_size: 0.05,
+                    sun_intensity: 20.,
+                    image_path: String::new(),
+                    image_rotation: 0.,
+                    image_exposure: 1.,
+                    sample_as_light: false,
+                    light_sample_factor: 1.,
+                    light_sample_max_smoothness: 0.5,
+                    image: Option::None,
+                    image_cdf: vec![],
+                },
             },
             sphere_parameters: vec![
                 SphereParameters {
@@ -447,6 +960,8 @@ impl Parameters {
                     max_smoothness: 1.,
                     min_refractivity_index: 1.,
                     max_refractivity_index: 1.,
+                    normal_map: NormalMap::none(),
+                    density: 1.,
                 },
                 SphereParameters {
                     sphere_type: SphereType::Refractive,
@@ -460,10 +975,20 @@ impl Parameters {
                     max_smoothness: 1.,
                     min_refractivity_index: 1.05,
                     max_refractivity_index: 2.,
+                    normal_map: NormalMap::none(),
+                    density: 1.,
                 },
             ],
             physics_parameters: PhysicsParameters {
-                g: 0.002,
+                gravity: Position {
+                    x: 0.,
+                    y: 0.,
+                    z: 7.2,
+                },
+                integrator: Integrator::SemiImplicitEuler,
+                fixed_timestep: 1. / 120.,
+                max_substeps: 8,
+                time_scale: 1.,
                 enabled: false,
                 min_x: -physics_bounds_value,
                 max_x: physics_bounds_value,
@@ -477,14 +1002,60 @@ impl Parameters {
                 max_vy: speed_bounds_value,
                 min_vz: -speed_bounds_value,
                 max_vz: speed_bounds_value,
+                collisions: true,
+                restitution: 0.9,
+                friction: 0.1,
+                grid_cell_size: 0.,
+                n_body: false,
+                gravitational_constant: 1.,
+                softening: 0.5,
+                barnes_hut_theta: 0.5,
+                barnes_hut_min_count: 128,
+            },
+            denoiser_parameters: DenoiserParameters {
+                enabled: false,
+                iterations: 4,
+                sigma_color: 400.,
+                sigma_normal: 16.,
+                sigma_depth: 0.5,
+                sigma_albedo: 20.,
+            },
+            tile_parameters: TileParameters {
+                enabled: false,
+                tile_size: 16,
+                order: TileOrder::Spiral,
+                batch_time_ms: 50.,
+            },
+            hud_parameters: HudParameters {
+                enabled: false,
+                text_scale: 2,
+                update_interval_ms: 500.,
+            },
+            camera_path: CameraPath {
+                keyframes: vec![],
+                interpolation: PathInterpolation::CatmullRom,
+                keyframe_interval: 1.,
             },
+            key_bindings: KeyBindings::default(),
         };
     }
 
+    // Size in pixels of the displayed image, before the display scale
+    pub fn get_output_size(&self) -> (i64, i64) {
+        return self.observer_parameters.stereo_mode.get_output_size(
+            self.ray_parameters.max_hor_value - self.ray_parameters.min_hor_value,
+            self.ray_parameters.max_ver_value - self.ray_parameters.min_ver_value,
+        );
+    }
+
     pub fn get_from_json(default_params: Option<Self>) -> Self {
-        println!("Reading parameters file...");
-        let str: String = fs::read_to_string(get_parameter_file_path().to_string())
-            .expect("Unable to read parameter file");
+        return Parameters::get_from_json_file(&get_parameter_file_path(), default_params);
+    }
+
+    // Any scene file, in the same format as parameters.json
+    pub fn get_from_json_file(path: &str, default_params: Option<Self>) -> Self {
+        println!("Reading parameters file {}...", path);
+        let str: String = fs::read_to_string(path).expect("Unable to read parameter file");
 
         let data: Value = serde_json::from_str(&str).expect("JSON was not well-formatted");
 
@@ -516,6 +1087,23 @@ impl Parameters {
                 &data["physics_parameters"],
                 &default_params.physics_parameters,
             ),
+            denoiser_parameters: DenoiserParameters::get_from_json(
+                &data["denoiser_parameters"],
+                &default_params.denoiser_parameters,
+            ),
+            tile_parameters: TileParameters::get_from_json(
+                &data["tile_parameters"],
+                &default_params.tile_parameters,
+            ),
+            hud_parameters: HudParameters::get_from_json(
+                &data["hud_parameters"],
+                &default_params.hud_parameters,
+            ),
+            camera_path: camera_path_from_json(&data["camera_path"], &default_params.camera_path),
+            key_bindings: KeyBindings::get_from_json(
+                &data["key_bindings"],
+                &default_params.key_bindings,
+            ),
         };
     }
 }
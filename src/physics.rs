@@ -0,0 +1,446 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Instant,
+};
+
+use rayon::prelude::*;
+
+use crate::{
+    octree::Octree, parameters::PhysicsParameters, position::Position, speed::Speed, sphere::Sphere,
+};
+
+#[derive(Clone, Copy)]
+pub enum Integrator {
+    ExplicitEuler,
+    SemiImplicitEuler,
+    Verlet,
+}
+
+impl Integrator {
+    pub fn to_string(&self) -> &str {
+        return match *self {
+            Self::ExplicitEuler => "ExplicitEuler",
+            Self::SemiImplicitEuler => "SemiImplicitEuler",
+            Self::Verlet => "Verlet",
+        };
+    }
+
+    pub fn from_string(string: &str) -> Self {
+        return match string {
+            "ExplicitEuler" => Self::ExplicitEuler,
+            "SemiImplicitEuler" => Self::SemiImplicitEuler,
+            "Verlet" => Self::Verlet,
+            _ => Self::SemiImplicitEuler,
+        };
+    }
+}
+
+// Steps of a single advance, so that a huge time scale can't hang a recording
+const MAX_ADVANCE_STEPS: u64 = 100_000;
+
+// Runs the physics by fixed steps, so that the simulation goes at the same speed whatever the frame rate
+pub struct PhysicsClock {
+    accumulator: f64,
+    last_update: Option<Instant>,
+}
+
+impl PhysicsClock {
+    pub fn new() -> PhysicsClock {
+        return PhysicsClock {
+            accumulator: 0.,
+            last_update: Option::None,
+        };
+    }
+
+    // The time spent paused is not caught up when the physics start again
+    pub fn pause(&mut self) {
+        self.last_update = Option::None;
+    }
+
+    // Catches up with the wall-clock time since the last update, returns the number of steps
+    pub fn update(
+        &mut self,
+        sphere_vector: &mut Vec<Sphere>,
+        physics_parameters: &PhysicsParameters,
+    ) -> u64 {
+        let now: Instant = Instant::now();
+        let elapsed: f64 = match self.last_update {
+            Some(last_update) => now.duration_since(last_update).as_secs_f64(),
+            None => 0.,
+        };
+        self.last_update = Option::Some(now);
+
+        let steps: u64 = self.run(
+            elapsed,
+            physics_parameters.max_substeps.max(1),
+            sphere_vector,
+            physics_parameters,
+        );
+
+        // Too far behind: the simulation slows down rather than taking the whole frame time
+        let timestep: f64 = Self::get_timestep(physics_parameters);
+        if self.accumulator >= timestep {
+            self.accumulator %= timestep;
+        }
+
+        return steps;
+    }
+
+    // Simulated time instead of the wall clock, every step is run up to MAX_ADVANCE_STEPS
+    pub fn advance(
+        &mut self,
+        seconds: f64,
+        sphere_vector: &mut Vec<Sphere>,
+        physics_parameters: &PhysicsParameters,
+    ) -> u64 {
+        if !(seconds * physics_parameters.time_scale).is_finite() {
+            println!("Physics not advanced: the time to simulate is not finite");
+            return 0;
+        }
+
+        let steps: u64 = self.run(
+            seconds,
+            MAX_ADVANCE_STEPS,
+            sphere_vector,
+            physics_parameters,
+        );
+
+        // A huge time scale would take forever, the rest of the time is dropped
+        let timestep: f64 = Self::get_timestep(physics_parameters);
+        if self.accumulator >= timestep {
+            println!(
+                "Physics stopped after {} steps, {:.3e}s of simulated time dropped",
+                steps, self.accumulator
+            );
+            self.accumulator %= timestep;
+        }
+
+        return steps;
+    }
+
+    fn get_timestep(physics_parameters: &PhysicsParameters) -> f64 {
+        return physics_parameters.fixed_timestep.max(1e-6);
+    }
+
+    fn run(
+        &mut self,
+        seconds: f64,
+        max_steps: u64,
+        sphere_vector: &mut Vec<Sphere>,
+        physics_parameters: &PhysicsParameters,
+    ) -> u64 {
+        let timestep: f64 = Self::get_timestep(physics_parameters);
+        self.accumulator += seconds * physics_parameters.time_scale;
+
+        let mut steps: u64 = 0;
+        while self.accumulator >= timestep && steps < max_steps {
+            step(sphere_vector, timestep, physics_parameters);
+            self.accumulator -= timestep;
+            steps += 1;
+        }
+
+        return steps;
+    }
+}
+
+fn speed_to_position(speed: &Speed) -> Position {
+    return Position {
+        x: speed.x,
+        y: speed.y,
+        z: speed.z,
+    };
+}
+
+fn position_to_speed(position: &Position) -> Speed {
+    return Speed {
+        x: position.x,
+        y: position.y,
+        z: position.z,
+    };
+}
+
+// A sphere with a density of 0 is fixed, nothing can push it
+fn get_inverse_mass(sphere: &Sphere) -> f64 {
+    let mass: f64 = sphere.mass();
+    return if mass > 0. { 1. / mass } else { 0. };
+}
+
+// Softened inverse-square attraction of a mass, the softening keeps close encounters from exploding
+pub fn get_attraction(
+    delta: &Position,
+    mass: f64,
+    physics_parameters: &PhysicsParameters,
+) -> Position {
+    let softening: f64 = physics_parameters.softening;
+    return delta.scaled(
+        physics_parameters.gravitational_constant * mass
+            / (delta.dot(delta) + softening * softening)
+                .powf(1.5)
+                .max(1e-12),
+    );
+}
+
+// Speed of a circular orbit at the given distance around a mass
+pub fn get_orbital_speed(mass: f64, distance: f64, physics_parameters: &PhysicsParameters) -> f64 {
+    let delta: Position = Position {
+        x: distance,
+        y: 0.,
+        z: 0.,
+    };
+    return (get_attraction(&delta, mass, physics_parameters).x * distance).sqrt();
+}
+
+// The mutual attraction of the spheres, pairwise or with the Barnes-Hut approximation for large counts
+fn get_n_body_accelerations(
+    sphere_vector: &Vec<Sphere>,
+    physics_parameters: &PhysicsParameters,
+) -> Vec<Position> {
+    let positions: Vec<Position> = sphere_vector.iter().map(|s: &Sphere| s.pos).collect();
+    let masses: Vec<f64> = sphere_vector.iter().map(|s: &Sphere| s.mass()).collect();
+
+    if physics_parameters.barnes_hut_theta > 0.
+        && sphere_vector.len() as u64 >= physics_parameters.barnes_hut_min_count
+    {
+        let octree: Octree = Octree::new(&positions, &masses);
+        return positions
+            .par_iter()
+            .map(|position: &Position| {
+                octree.get_acceleration(
+                    position,
+                    physics_parameters.gravitational_constant,
+                    physics_parameters.softening,
+                    physics_parameters.barnes_hut_theta,
+                )
+            })
+            .collect();
+    }
+
+    return positions
+        .par_iter()
+        .enumerate()
+        .map(|(i, position): (usize, &Position)| {
+            let mut acceleration: Position = Position {
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            };
+            for (j, other) in positions.iter().enumerate() {
+                if i != j && masses[j] > 0. {
+                    acceleration = acceleration
+                        + get_attraction(&(*other - *position), masses[j], physics_parameters);
+                }
+            }
+            acceleration
+        })
+        .collect();
+}
+
+fn get_accelerations(
+    sphere_vector: &Vec<Sphere>,
+    physics_parameters: &PhysicsParameters,
+) -> Vec<Position> {
+    if !physics_parameters.n_body {
+        return vec![physics_parameters.gravity; sphere_vector.len()];
+    }
+
+    return get_n_body_accelerations(sphere_vector, physics_parameters)
+        .iter()
+        .map(|acceleration: &Position| *acceleration + physics_parameters.gravity)
+        .collect();
+}
+
+// Moves every sphere by one physics step of dt seconds, then separates the ones that overlap
+pub fn step(sphere_vector: &mut Vec<Sphere>, dt: f64, physics_parameters: &PhysicsParameters) {
+    let accelerations: Vec<Position> = get_accelerations(sphere_vector, physics_parameters);
+
+    match physics_parameters.integrator {
+        Integrator::ExplicitEuler => {
+            for (s, acceleration) in sphere_vector.iter_mut().zip(accelerations.iter()) {
+                if get_inverse_mass(s) > 0. {
+                    let speed: Position = speed_to_position(&s.speed);
+                    s.pos = s.pos + speed.scaled(dt);
+                    s.speed = position_to_speed(&(speed + acceleration.scaled(dt)));
+                }
+            }
+        }
+        Integrator::SemiImplicitEuler => {
+            for (s, acceleration) in sphere_vector.iter_mut().zip(accelerations.iter()) {
+                if get_inverse_mass(s) > 0. {
+                    let speed: Position = speed_to_position(&s.speed) + acceleration.scaled(dt);
+                    s.pos = s.pos + speed.scaled(dt);
+                    s.speed = position_to_speed(&speed);
+                }
+            }
+        }
+        Integrator::Verlet => {
+            // Velocity Verlet, the speed uses the average of the accelerations before and after the move
+            for (s, acceleration) in sphere_vector.iter_mut().zip(accelerations.iter()) {
+                if get_inverse_mass(s) > 0. {
+                    s.pos = s.pos
+                        + speed_to_position(&s.speed).scaled(dt)
+                        + acceleration.scaled(0.5 * dt * dt);
+                }
+            }
+            let new_accelerations: Vec<Position> =
+                get_accelerations(sphere_vector, physics_parameters);
+            for ((s, acceleration), new_acceleration) in sphere_vector
+                .iter_mut()
+                .zip(accelerations.iter())
+                .zip(new_accelerations.iter())
+            {
+                if get_inverse_mass(s) > 0. {
+                    s.speed = position_to_speed(
+                        &(speed_to_position(&s.speed)
+                            + (*acceleration + *new_acceleration).scaled(0.5 * dt)),
+                    );
+                }
+            }
+        }
+    }
+
+    for s in sphere_vector.iter_mut() {
+        s.bounce_on_walls(physics_parameters);
+    }
+
+    if physics_parameters.collisions {
+        for (i, j) in get_collision_candidates(sphere_vector, physics_parameters) {
+            collide(sphere_vector, i, j, physics_parameters);
+        }
+    }
+}
+
+// Broad phase: pairs of spheres sharing a cell of a uniform grid, each sphere is in every cell its bounding box touches
+fn get_collision_candidates(
+    sphere_vector: &Vec<Sphere>,
+    physics_parameters: &PhysicsParameters,
+) -> Vec<(usize, usize)> {
+    let cell_size: f64 = if physics_parameters.grid_cell_size > 0. {
+        physics_parameters.grid_cell_size
+    } else {
+        // The biggest sphere fits in a cell
+        2. * sphere_vector
+            .iter()
+            .fold(0., |max: f64, s: &Sphere| max.max(s.radius))
+    };
+    if cell_size <= 0. {
+        return vec![];
+    }
+
+    let cell = |value: f64| -> i64 { (value / cell_size).floor() as i64 };
+
+    let mut grid: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+    for (index, s) in sphere_vector.iter().enumerate() {
+        for x in cell(s.pos.x - s.radius)..=cell(s.pos.x + s.radius) {
+            for y in cell(s.pos.y - s.radius)..=cell(s.pos.y + s.radius) {
+                for z in cell(s.pos.z - s.radius)..=cell(s.pos.z + s.radius) {
+                    grid.entry((x, y, z)).or_insert(vec![]).push(index);
+                }
+            }
+        }
+    }
+
+    let mut pairs: HashSet<(usize, usize)> = HashSet::new();
+    for indices in grid.values() {
+        for (k, i) in indices.iter().enumerate() {
+            for j in indices[k + 1..].iter() {
+                pairs.insert((*i.min(j), *i.max(j)));
+            }
+        }
+    }
+
+    // Sorted so that the simulation doesn't depend on the order of the hash map
+    let mut pairs: Vec<(usize, usize)> = pairs.into_iter().collect();
+    pairs.sort();
+    return pairs;
+}
+
+// Narrow phase: pushes the spheres apart and exchanges an impulse along the normal, friction along the contact
+fn collide(
+    sphere_vector: &mut Vec<Sphere>,
+    i: usize,
+    j: usize,
+    physics_parameters: &PhysicsParameters,
+) {
+    let (a, b): (Sphere, Sphere) = (sphere_vector[i], sphere_vector[j]);
+
+    let delta: Position = b.pos - a.pos;
+    let distance: f64 = delta.length();
+    let penetration: f64 = a.radius + b.radius - distance;
+    if penetration <= 0. {
+        return;
+    }
+
+    let inverse_mass_a: f64 = get_inverse_mass(&a);
+    let inverse_mass_b: f64 = get_inverse_mass(&b);
+    let inverse_mass_sum: f64 = inverse_mass_a + inverse_mass_b;
+    if inverse_mass_sum <= 0. {
+        return;
+    }
+
+    let normal: Position = if distance > 0. {
+        delta.scaled(1. / distance)
+    } else {
+        Position {
+            x: 1.,
+            y: 0.,
+            z: 0.,
+        }
+    };
+
+    let mut pos_a: Position =
+        a.pos - normal.scaled(penetration * inverse_mass_a / inverse_mass_sum);
+    let mut pos_b: Position =
+        b.pos + normal.scaled(penetration * inverse_mass_b / inverse_mass_sum);
+    let mut speed_a: Position = speed_to_position(&a.speed);
+    let mut speed_b: Position = speed_to_position(&b.speed);
+
+    let relative_speed: Position = speed_b - speed_a;
+    let normal_speed: f64 = relative_speed.dot(&normal);
+
+    // Only when they get closer, separating spheres are left alone
+    if normal_speed < 0. {
+        let normal_impulse: f64 =
+            -(1. + physics_parameters.restitution) * normal_speed / inverse_mass_sum;
+        speed_a = speed_a - normal.scaled(normal_impulse * inverse_mass_a);
+        speed_b = speed_b + normal.scaled(normal_impulse * inverse_mass_b);
+
+        // Coulomb friction, it can at most stop the sliding
+        let tangent_speed: Position = relative_speed - normal.scaled(normal_speed);
+        let tangent_length: f64 = tangent_speed.length();
+        if tangent_length > 1e-12 {
+            let tangent: Position = tangent_speed.scaled(1. / tangent_length);
+            let friction_impulse: f64 = (tangent_length / inverse_mass_sum)
+                .min(physics_parameters.friction * normal_impulse);
+            speed_a = speed_a + tangent.scaled(friction_impulse * inverse_mass_a);
+            speed_b = speed_b - tangent.scaled(friction_impulse * inverse_mass_b);
+        }
+    }
+
+    // The box walls win over the separation
+    pos_a = clamp_to_bounds(&pos_a, a.radius, physics_parameters);
+    pos_b = clamp_to_bounds(&pos_b, b.radius, physics_parameters);
+
+    sphere_vector[i].pos = pos_a;
+    sphere_vector[i].speed = position_to_speed(&speed_a);
+    sphere_vector[j].pos = pos_b;
+    sphere_vector[j].speed = position_to_speed(&speed_b);
+}
+
+fn clamp_to_bounds(
+    pos: &Position,
+    radius: f64,
+    physics_parameters: &PhysicsParameters,
+) -> Position {
+    let clamp = |value: f64, min: f64, max: f64| -> f64 {
+        if min + radius > max - radius {
+            return value;
+        }
+        return value.clamp(min + radius, max - radius);
+    };
+
+    return Position {
+        x: clamp(pos.x, physics_parameters.min_x, physics_parameters.max_x),
+        y: clamp(pos.y, physics_parameters.min_y, physics_parameters.max_y),
+        z: clamp(pos.z, physics_parameters.min_z, physics_parameters.max_z),
+    };
+}
@@ -59,7 +59,15 @@ impl Position {
         return (self.x * p.x) + (self.y * p.y) + (self.z * p.z);
     }
 
-    fn length(&self) -> f64 {
+    pub fn cross(&self, p: &Position) -> Position {
+        return Position {
+            x: (self.y * p.z) - (self.z * p.y),
+            y: (self.z * p.x) - (self.x * p.z),
+            z: (self.x * p.y) - (self.y * p.x),
+        };
+    }
+
+    pub fn length(&self) -> f64 {
         return self.dist(&Position {
             x: 0.,
             y: 0.,
@@ -1,3 +1,5 @@
+use rand::Rng;
+
 use crate::{
     parameters::RayParameters,
     position::Position,
@@ -17,6 +19,9 @@ pub struct Ray {
     pub refraction_factor: f64,
     pub x_value: i64,
     pub y_value: i64,
+    pub x_offset: f64,
+    pub y_offset: f64,
+    pub time: f64,
 }
 
 impl Ray {
@@ -32,24 +37,17 @@ impl Ray {
             refraction_factor: refraction_factor,
             x_value: x_value,
             y_value: y_value,
+            x_offset: 0.,
+            y_offset: 0.,
+            time: 0.,
         };
     }
 
-    pub fn new_turned(
-        p: Position,
-        d: Position,
-        refraction_factor: f64,
-        x_value: i64,
-        y_value: i64,
-        x_angle: f64,
-        y_angle: f64,
-        z_angle: f64,
-    ) -> Ray {
-        let mut r = Ray::new(p, d, refraction_factor, x_value, y_value);
-        r.turn_x(x_angle);
-        r.turn_y(y_angle);
-        r.turn_z(z_angle);
-        return r;
+    // Bounced rays keep the pixel and the time of the ray they come from
+    fn new_child(&self, p1: Position, p2: Position, refraction_factor: f64) -> Ray {
+        let mut ray: Ray = Ray::new(p1, p2, refraction_factor, self.x_value, self.y_value);
+        ray.time = self.time;
+        return ray;
     }
 
     fn update_vector(&mut self) {
@@ -80,24 +78,26 @@ impl Ray {
         s: &Sphere,
         ray_parameters: &RayParameters,
     ) -> (f64, bool) {
+        let center: Position = s.get_position_at(self.time);
+
         // These are the parts of a quadratic equation given by substituting
         // the values of the line (ray) into the equation for the given sphere
         let a: f64 = squared(self.vector.p2.x - self.vector.p1.x)
             + squared(self.vector.p2.y - self.vector.p1.y)
             + squared(self.vector.p2.z - self.vector.p1.z);
         let b: f64 = 2.
-            * ((self.vector.p2.x - self.vector.p1.x) * (self.vector.p1.x - s.pos.x)
-                + (self.vector.p2.y - self.vector.p1.y) * (self.vector.p1.y - s.pos.y)
-                + (self.vector.p2.z - self.vector.p1.z) * (self.vector.p1.z - s.pos.z));
-        let c: f64 = squared(s.pos.x)
-            + squared(s.pos.y)
-            + squared(s.pos.z)
+            * ((self.vector.p2.x - self.vector.p1.x) * (self.vector.p1.x - center.x)
+                + (self.vector.p2.y - self.vector.p1.y) * (self.vector.p1.y - center.y)
+                + (self.vector.p2.z - self.vector.p1.z) * (self.vector.p1.z - center.z));
+        let c: f64 = squared(center.x)
+            + squared(center.y)
+            + squared(center.z)
             + squared(self.vector.p1.x)
             + squared(self.vector.p1.y)
             + squared(self.vector.p1.z)
-            - 2. * (s.pos.x * self.vector.p1.x
-                + s.pos.y * self.vector.p1.y
-                + s.pos.z * self.vector.p1.z)
+            - 2. * (center.x * self.vector.p1.x
+                + center.y * self.vector.p1.y
+                + center.z * self.vector.p1.z)
             - squared(s.radius);
 
         let d = squared(b) - 4. * a * c;
@@ -156,19 +156,21 @@ impl Ray {
         return result;
     }
 
-    pub fn get_deviation(
+    pub fn get_deviation<R: Rng>(
         &self,
         intersection_factor: f64,
         is_entering: bool,
         sphere: &Sphere,
         ray_parameters: &RayParameters,
-        rng: &mut rand::prelude::ThreadRng,
+        rng: &mut R,
     ) -> Ray {
         return self.apply_smoothness(
             match sphere.type_ {
-                SphereType::Reflexive => self.get_reflection(intersection_factor, sphere),
+                SphereType::Reflexive => {
+                    self.get_reflection(intersection_factor, sphere, ray_parameters)
+                }
                 SphereType::Refractive => {
-                    self.get_refraction(intersection_factor, is_entering, sphere)
+                    self.get_refraction(intersection_factor, is_entering, sphere, ray_parameters)
                 }
             },
             sphere,
@@ -177,12 +179,12 @@ impl Ray {
         );
     }
 
-    fn apply_smoothness(
+    fn apply_smoothness<R: Rng>(
         &self,
         mut ray: Ray,
         sphere: &Sphere,
         ray_parameters: &RayParameters,
-        rng: &mut rand::prelude::ThreadRng,
+        rng: &mut R,
     ) -> Ray {
         let smoothness_factor: f64 = 1. - sphere.smoothness;
 
@@ -205,24 +207,29 @@ impl Ray {
         return ray;
     }
 
-    fn get_reflection(&self, intersection_factor: f64, sphere: &Sphere) -> Ray {
+    fn get_reflection(
+        &self,
+        intersection_factor: f64,
+        sphere: &Sphere,
+        ray_parameters: &RayParameters,
+    ) -> Ray {
         let intersection = self.get_position_from_factor(intersection_factor);
-        let u = intersection - sphere.pos;
+        let u = sphere.get_normal(&intersection, ray_parameters);
         let v = intersection - self.vector.p1;
         let w = u.scaled(-(v.dot(&u) / u.dot(&u)));
         let direction = (intersection + w).scaled(2.) - self.vector.p1;
 
-        return Ray::new(
-            intersection,
-            direction,
-            self.refraction_factor,
-            self.x_value,
-            self.y_value,
-        );
+        return self.new_child(intersection, direction, self.refraction_factor);
     }
 
     // I hope I never have to debug this...
-    fn get_refraction(&self, intersection_factor: f64, is_entering: bool, sphere: &Sphere) -> Ray {
+    fn get_refraction(
+        &self,
+        intersection_factor: f64,
+        is_entering: bool,
+        sphere: &Sphere,
+        ray_parameters: &RayParameters,
+    ) -> Ray {
         let (n1, n2) = if is_entering {
             (self.refraction_factor, sphere.refractivity_index)
         } else {
@@ -230,8 +237,7 @@ impl Ray {
         };
 
         let intersection: Position = self.get_position_from_factor(intersection_factor);
-        let normal_sphere: Position =
-            (self.get_position_from_factor(intersection_factor) - sphere.pos).normalized();
+        let normal_sphere: Position = sphere.get_normal(&intersection, ray_parameters);
         let (normal, normal2) = if is_entering {
             (-normal_sphere, normal_sphere)
         } else {
@@ -243,7 +249,7 @@ impl Ray {
         let angle_incident = incident.angle(&normal);
 
         if angle_incident.sin() > n2 / n1 {
-            return self.get_reflection(intersection_factor, sphere);
+            return self.get_reflection(intersection_factor, sphere, ray_parameters);
         }
 
         let angle_exit: f64 = f64::asin((n1 * f64::sin(angle_incident)) / n2);
@@ -256,7 +262,7 @@ impl Ray {
             + (incident.scaled(a) + normal.scaled(-(a * incident.dot(&normal))))
                 .scaled(f64::sin(angle_exit)));
 
-        return Ray::new(
+        return self.new_child(
             intersection,
             intersection + exit,
             if is_entering {
@@ -264,8 +270,6 @@ impl Ray {
             } else {
                 1.
             },
-            self.x_value,
-            self.y_value,
         );
     }
 }
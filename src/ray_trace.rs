@@ -1,12 +1,20 @@
-use rand::rngs::ThreadRng;
+use rand::{rngs::SmallRng, Rng};
 use sdl2::pixels::Color;
 
-use crate::{parameters::RayParameters, ray::Ray, sphere::Sphere};
+use crate::{
+    environment, parameters::RayParameters, position::Position, ray::Ray, render_pass::SurfaceInfo,
+    sphere::Sphere, util,
+};
 
 pub struct RayTrace<'a> {
     pub ray: &'a Ray,
     color_vector: Vec<(Color, f64)>,
     pub color: Color,
+    pub sample_environment_light: bool,
+    // Seed of the random bounces, for reproducible renders
+    pub seed: Option<u64>,
+    pub surface: Option<SurfaceInfo>,
+    pub bounces: u64,
 }
 
 impl<'a> RayTrace<'a> {
@@ -15,20 +23,30 @@ impl<'a> RayTrace<'a> {
             ray: ray,
             color_vector: Vec::new(),
             color: ray_parameters.background_color,
+            sample_environment_light: false,
+            seed: Option::None,
+            surface: Option::None,
+            bounces: 0,
         };
     }
 
     pub fn trace(&mut self, sphere_vector: &Vec<&Sphere>, ray_parameters: &RayParameters) {
-        let mut rng: ThreadRng = rand::thread_rng();
+        let mut rng: SmallRng = util::get_rng(self.seed);
         self.trace_rec(
             self.ray,
             sphere_vector,
             ray_parameters,
             ray_parameters.bounce_count,
             &0.,
+            false,
             &mut rng,
         );
 
+        match &mut self.surface {
+            Some(surface) => surface.bounces = self.bounces,
+            None => {}
+        }
+
         self.set_color(ray_parameters)
     }
 
@@ -38,31 +56,50 @@ impl<'a> RayTrace<'a> {
         }
     }
 
-    fn trace_rec(
+    fn trace_rec<R: Rng>(
         &mut self,
         ray: &Ray,
         sphere_vector: &Vec<&Sphere>,
         ray_parameters: &RayParameters,
         remaining_bounces: u64,
         distance: &f64,
-        rng: &mut rand::prelude::ThreadRng,
+        light_sampled: bool,
+        rng: &mut R,
     ) {
         let collision: Option<((f64, bool), &Sphere)> =
             ray.find_collision(sphere_vector, ray_parameters);
 
         match collision {
             None => {
-                if (ray_parameters.reflect_background)
-                    && (remaining_bounces > 0)
-                    && (*distance > 0.)
+                let environment_color: (Color, f64) = environment::get_color(
+                    &ray_parameters.environment,
+                    &ray_parameters.background_color,
+                    ray_parameters.background_light_factor,
+                    &ray.vector.as_position(),
+                );
+
+                // The light sample of the previous hit already brought the light from there
+                let counted: bool = light_sampled
+                    && environment::is_sampled_as_light(
+                        &ray_parameters.environment,
+                        &ray.vector.as_position(),
+                    );
+
+                if *distance == 0. {
+                    self.color = environment_color.0;
+                } else if (ray_parameters.reflect_background) && (remaining_bounces > 0) && !counted
                 {
-                    self.color_vector.push((
-                        ray_parameters.background_color,
-                        ray_parameters.background_light_factor,
-                    ));
+                    self.color_vector.push(environment_color);
                 }
             }
             Some(((factor, is_front), sphere)) => {
+                if *distance == 0. {
+                    self.set_surface(ray, factor, is_front, sphere, sphere_vector, ray_parameters);
+                }
+                self.bounces += 1;
+
+                // With motion blur the sphere is hit where it was at the time of the ray
+                let sphere: &Sphere = &sphere.at_time(ray.time);
                 let new_distance: f64 = distance + (ray.vector.length * factor);
 
                 self.color_vector.push((
@@ -73,6 +110,22 @@ impl<'a> RayTrace<'a> {
                     sphere.light_factor,
                 ));
 
+                // Only rough surfaces sample the light, glossy ones and mirrors keep their reflection
+                let light_sampled: bool = self.sample_environment_light
+                    && sphere.smoothness < 1.
+                    && sphere.smoothness <= ray_parameters.environment.light_sample_max_smoothness;
+                if light_sampled {
+                    self.sample_light(
+                        ray,
+                        factor,
+                        is_front,
+                        sphere,
+                        sphere_vector,
+                        ray_parameters,
+                        rng,
+                    );
+                }
+
                 if remaining_bounces > 0 {
                     let ray_bounce =
                         ray.get_deviation(factor, is_front, sphere, ray_parameters, rng);
@@ -83,6 +136,7 @@ impl<'a> RayTrace<'a> {
                         ray_parameters,
                         remaining_bounces - 1,
                         &new_distance,
+                        light_sampled,
                         rng,
                     );
                 }
@@ -90,6 +144,85 @@ impl<'a> RayTrace<'a> {
         }
     }
 
+    fn set_surface(
+        &mut self,
+        ray: &Ray,
+        factor: f64,
+        is_front: bool,
+        sphere: &Sphere,
+        sphere_vector: &Vec<&Sphere>,
+        ray_parameters: &RayParameters,
+    ) {
+        let position: Position = ray.get_position_from_factor(factor);
+        let moved_sphere: Sphere = sphere.at_time(ray.time);
+        let normal: Position = moved_sphere.get_normal(&position, ray_parameters);
+
+        self.surface = Option::Some(SurfaceInfo {
+            position: position,
+            depth: ray.vector.length * factor,
+            normal: if is_front { normal } else { -normal },
+            albedo: sphere.color,
+            object_id: sphere_vector
+                .iter()
+                .position(|s: &&Sphere| std::ptr::eq(*s, sphere))
+                .unwrap_or(0),
+            bounces: 0,
+        });
+    }
+
+    // Direct lighting from the environment, a shadow ray towards a bright part of it
+    fn sample_light<R: Rng>(
+        &mut self,
+        ray: &Ray,
+        factor: f64,
+        is_front: bool,
+        sphere: &Sphere,
+        sphere_vector: &Vec<&Sphere>,
+        ray_parameters: &RayParameters,
+        rng: &mut R,
+    ) {
+        let (direction, pdf): (Position, f64) =
+            match environment::sample_light_direction(&ray_parameters.environment, rng) {
+                Some(sample) => sample,
+                None => return,
+            };
+
+        let intersection: Position = ray.get_position_from_factor(factor);
+        let mut normal: Position = sphere.get_normal(&intersection, ray_parameters);
+        if !is_front {
+            normal = -normal;
+        }
+
+        let cos: f64 = normal.dot(&direction);
+        if cos <= 0. {
+            return;
+        }
+
+        let origin: Position = intersection + normal.scaled(1e-6);
+        let mut shadow_ray: Ray =
+            Ray::new(origin, origin + direction, 1., ray.x_value, ray.y_value);
+        shadow_ray.time = ray.time;
+
+        if shadow_ray
+            .find_collision(sphere_vector, ray_parameters)
+            .is_none()
+        {
+            let (color, light_factor) = environment::get_color(
+                &ray_parameters.environment,
+                &ray_parameters.background_color,
+                ray_parameters.background_light_factor,
+                &direction,
+            );
+
+            // Divided by the pdf of the direction, and by pi like a diffuse bounce, whose rays follow the cosine
+            self.color_vector.push((
+                color,
+                light_factor * cos / (std::f64::consts::PI * pdf)
+                    * ray_parameters.environment.light_sample_factor,
+            ));
+        }
+    }
+
     fn get_average_color(&self, importance_factor: &f64) -> Color {
         let mut r: f64 = 0.;
         let mut g: f64 = 0.;
@@ -0,0 +1,117 @@
+use sdl2::pixels::Color;
+
+use crate::position::Position;
+
+// What the primary ray of a pixel hit first
+#[derive(Clone, Copy)]
+pub struct SurfaceInfo {
+    pub position: Position,
+    pub depth: f64,
+    pub normal: Position,
+    pub albedo: Color,
+    pub object_id: usize,
+    pub bounces: u64,
+}
+
+#[derive(Clone, Copy)]
+pub enum RenderPass {
+    Color,
+    Depth,
+    Normal,
+    Albedo,
+    ObjectId,
+    BounceCount,
+    SampleCount,
+}
+
+impl RenderPass {
+    pub fn to_string(&self) -> &str {
+        return match *self {
+            Self::Color => "Color",
+            Self::Depth => "Depth",
+            Self::Normal => "Normal",
+            Self::Albedo => "Albedo",
+            Self::ObjectId => "ObjectId",
+            Self::BounceCount => "BounceCount",
+            Self::SampleCount => "SampleCount",
+        };
+    }
+
+    pub fn from_string(string: &str) -> Self {
+        return match string {
+            "Color" => Self::Color,
+            "Depth" => Self::Depth,
+            "Normal" => Self::Normal,
+            "Albedo" => Self::Albedo,
+            "ObjectId" => Self::ObjectId,
+            "BounceCount" => Self::BounceCount,
+            "SampleCount" => Self::SampleCount,
+            _ => Self::Color,
+        };
+    }
+
+    pub fn all() -> Vec<RenderPass> {
+        return vec![
+            Self::Color,
+            Self::Depth,
+            Self::Normal,
+            Self::Albedo,
+            Self::ObjectId,
+            Self::BounceCount,
+            Self::SampleCount,
+        ];
+    }
+
+    pub fn next(&self) -> Self {
+        return match *self {
+            Self::Color => Self::Depth,
+            Self::Depth => Self::Normal,
+            Self::Normal => Self::Albedo,
+            Self::Albedo => Self::ObjectId,
+            Self::ObjectId => Self::BounceCount,
+            Self::BounceCount => Self::SampleCount,
+            Self::SampleCount => Self::Color,
+        };
+    }
+
+    // Displayable color of an auxiliary value, max_depth and max_bounces are used for scaling
+    // The color and the sample count are not surface values, they come from the frame itself
+    pub fn get_color(&self, surface: &SurfaceInfo, max_depth: f64, max_bounces: u64) -> Color {
+        return match *self {
+            Self::Color | Self::SampleCount => surface.albedo,
+            Self::Depth => {
+                let value: u8 = (255. * (1. - surface.depth / max_depth).clamp(0., 1.)) as u8;
+                Color::RGB(value, value, value)
+            }
+            Self::Normal => Color::RGB(
+                ((surface.normal.x * 0.5 + 0.5) * 255.) as u8,
+                ((surface.normal.y * 0.5 + 0.5) * 255.) as u8,
+                ((surface.normal.z * 0.5 + 0.5) * 255.) as u8,
+            ),
+            Self::Albedo => surface.albedo,
+            Self::ObjectId => {
+                let hash: u64 = (surface.object_id as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15);
+                Color::RGB((hash >> 16) as u8, (hash >> 32) as u8, (hash >> 48) as u8)
+            }
+            Self::BounceCount => heat_color(surface.bounces as f64 / max_bounces.max(1) as f64),
+        };
+    }
+}
+
+// Blue to red through green, for values in [0, 1]
+pub fn heat_color(value: f64) -> Color {
+    let value: f64 = value.clamp(0., 1.);
+    return if value < 0.5 {
+        Color::RGB(
+            0,
+            (value * 2. * 255.) as u8,
+            ((1. - value * 2.) * 255.) as u8,
+        )
+    } else {
+        Color::RGB(
+            ((value - 0.5) * 2. * 255.) as u8,
+            ((1. - (value - 0.5) * 2.) * 255.) as u8,
+            0,
+        )
+    };
+}
@@ -1,8 +1,10 @@
-use rand::{rngs::ThreadRng, Rng};
+use rand::Rng;
 use sdl2::pixels::Color;
 
 use crate::{
-    parameters::{PhysicsParameters, SphereGenerationMode, SphereParameters},
+    normal_map::NormalMap,
+    parameters::{PhysicsParameters, RayParameters, SphereGenerationMode, SphereParameters},
+    physics::get_orbital_speed,
     position::Position,
     speed::Speed,
     util::{at_ratio, float_to_color, rand_color, rand_range},
@@ -41,7 +43,9 @@ pub struct Sphere {
     pub type_: SphereType,
     pub smoothness: f64,
     pub refractivity_index: f64,
+    pub normal_map: NormalMap,
     pub is_visible: bool,
+    pub density: f64,
 }
 
 impl Sphere {
@@ -67,7 +71,9 @@ impl Sphere {
             type_: SphereType::Reflexive,
             smoothness: 1.,
             refractivity_index: 1.,
+            normal_map: NormalMap::none(),
             is_visible: true,
+            density: 1.,
         });
         // Red
         v.push(Sphere {
@@ -87,7 +93,9 @@ impl Sphere {
             type_: SphereType::Reflexive,
             smoothness: 0.85,
             refractivity_index: 1.,
+            normal_map: NormalMap::none(),
             is_visible: true,
+            density: 1.,
         });
         // Green
         v.push(Sphere {
@@ -107,7 +115,9 @@ impl Sphere {
             type_: SphereType::Reflexive,
             smoothness: 0.92,
             refractivity_index: 1.,
+            normal_map: NormalMap::none(),
             is_visible: true,
+            density: 1.,
         });
         // White Refractive
         v.push(Sphere {
@@ -127,7 +137,9 @@ impl Sphere {
             type_: SphereType::Refractive,
             smoothness: 1.,
             refractivity_index: 10.,
+            normal_map: NormalMap::none(),
             is_visible: true,
+            density: 1.,
         });
         // Turquoise
         v.push(Sphere {
@@ -147,7 +159,9 @@ impl Sphere {
             type_: SphereType::Reflexive,
             smoothness: 1.,
             refractivity_index: 0.,
+            normal_map: NormalMap::none(),
             is_visible: true,
+            density: 1.,
         });
         // White
         v.push(Sphere {
@@ -167,7 +181,9 @@ impl Sphere {
             type_: SphereType::Refractive,
             smoothness: 1.,
             refractivity_index: 1.,
+            normal_map: NormalMap::none(),
             is_visible: true,
+            density: 1.,
         });
 
         return v;
@@ -191,10 +207,10 @@ impl Sphere {
         return v;
     }
 
-    pub fn random_vector(
+    pub fn random_vector<R: Rng>(
         sphere_parameters: &SphereParameters,
         physics_parameters: &PhysicsParameters,
-        rng: &mut rand::prelude::ThreadRng,
+        rng: &mut R,
     ) -> Vec<Sphere> {
         let mut v: Vec<Sphere> = vec![];
 
@@ -206,10 +222,10 @@ impl Sphere {
         return v;
     }
 
-    pub fn random(
+    pub fn random<R: Rng>(
         sphere_parameters: &SphereParameters,
         physics_parameters: &PhysicsParameters,
-        rng: &mut rand::prelude::ThreadRng,
+        rng: &mut R,
     ) -> Sphere {
         let radius_factor: f64 = rng.gen();
 
@@ -244,10 +260,168 @@ impl Sphere {
                 sphere_parameters.min_refractivity_index,
                 sphere_parameters.max_refractivity_index,
             ),
+            normal_map: sphere_parameters.normal_map,
             is_visible: true,
+            density: sphere_parameters.density,
         };
     }
 
+    // Center of the physics box and the radius of the biggest horizontal disk that fits in it
+    fn get_orbit_plane(physics_parameters: &PhysicsParameters) -> (Position, f64) {
+        let center: Position = Position {
+            x: (physics_parameters.min_x + physics_parameters.max_x) / 2.,
+            y: (physics_parameters.min_y + physics_parameters.max_y) / 2.,
+            z: (physics_parameters.min_z + physics_parameters.max_z) / 2.,
+        };
+        let radius: f64 = (physics_parameters.max_x - physics_parameters.min_x)
+            .min(physics_parameters.max_y - physics_parameters.min_y)
+            / 2.;
+
+        return (center, radius);
+    }
+
+    // On a circular orbit in the horizontal plane, counterclockwise
+    fn orbiting(&self, center: &Position, distance: f64, angle: f64, orbital_speed: f64) -> Sphere {
+        let mut sphere: Sphere = *self;
+        sphere.pos = Position {
+            x: center.x + distance * angle.cos(),
+            y: center.y + distance * angle.sin(),
+            z: self.pos.z,
+        };
+        sphere.speed = Speed {
+            x: -orbital_speed * angle.sin(),
+            y: orbital_speed * angle.cos(),
+            z: 0.,
+        };
+        return sphere;
+    }
+
+    // The central sphere moves against the others so that the whole system stays in place
+    fn cancel_momentum(v: &mut Vec<Sphere>) {
+        let mut momentum: Position = Position {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        };
+        for s in v[1..].iter() {
+            momentum = momentum
+                + Position {
+                    x: s.speed.x,
+                    y: s.speed.y,
+                    z: s.speed.z,
+                }
+                .scaled(s.mass());
+        }
+
+        let central_mass: f64 = v[0].mass();
+        if central_mass > 0. {
+            v[0].speed = Speed {
+                x: -momentum.x / central_mass,
+                y: -momentum.y / central_mass,
+                z: -momentum.z / central_mass,
+            };
+        }
+    }
+
+    // A sun of max_radius at the center of the physics box, and planets on circular orbits around it
+    pub fn solar_system_vector<R: Rng>(
+        sphere_parameters: &SphereParameters,
+        physics_parameters: &PhysicsParameters,
+        rng: &mut R,
+    ) -> Vec<Sphere> {
+        let mut v: Vec<Sphere> = vec![];
+        if sphere_parameters.sphere_count == 0 {
+            return v;
+        }
+
+        let (center, plane_radius) = Self::get_orbit_plane(physics_parameters);
+
+        let mut sun: Sphere = Sphere::random(sphere_parameters, physics_parameters, rng);
+        sun.pos = center;
+        sun.radius = sphere_parameters.max_radius;
+        sun.light_factor = sphere_parameters.max_light_factor;
+        v.push(sun);
+
+        let planet_count: u64 = sphere_parameters.sphere_count - 1;
+        let max_planet_radius: f64 = sphere_parameters
+            .min_radius
+            .max(sphere_parameters.max_radius * 0.4);
+        let first_orbit: f64 = sun.radius * 2.;
+        let last_orbit: f64 = (plane_radius - max_planet_radius) * 0.9;
+
+        for i in 0..planet_count {
+            let mut planet: Sphere = Sphere::random(sphere_parameters, physics_parameters, rng);
+            planet.radius = rand_range(rng, sphere_parameters.min_radius, max_planet_radius);
+            planet.pos.z = center.z;
+
+            let distance: f64 = if planet_count > 1 {
+                at_ratio(
+                    i as f64 / (planet_count - 1) as f64,
+                    first_orbit,
+                    last_orbit,
+                )
+            } else {
+                first_orbit
+            };
+            let orbital_speed: f64 = get_orbital_speed(sun.mass(), distance, physics_parameters);
+            let angle: f64 = rand_range(rng, 0., 2. * std::f64::consts::PI);
+
+            v.push(planet.orbiting(&center, distance, angle, orbital_speed));
+        }
+
+        Self::cancel_momentum(&mut v);
+        return v;
+    }
+
+    // A heavy core and a thin disk of stars along two spiral arms, each star orbiting the mass inside its orbit
+    pub fn galaxy_vector<R: Rng>(
+        sphere_parameters: &SphereParameters,
+        physics_parameters: &PhysicsParameters,
+        rng: &mut R,
+    ) -> Vec<Sphere> {
+        let mut v: Vec<Sphere> = vec![];
+        if sphere_parameters.sphere_count == 0 {
+            return v;
+        }
+
+        let (center, plane_radius) = Self::get_orbit_plane(physics_parameters);
+        let disk_radius: f64 = (plane_radius - sphere_parameters.max_radius) * 0.9;
+        let arm_twist: f64 = 2. * std::f64::consts::PI;
+
+        let mut core: Sphere = Sphere::random(sphere_parameters, physics_parameters, rng);
+        core.pos = center;
+        core.radius = sphere_parameters.max_radius;
+        core.light_factor = sphere_parameters.max_light_factor;
+        v.push(core);
+
+        let mut stars: Vec<(f64, f64, Sphere)> = vec![];
+        for i in 1..sphere_parameters.sphere_count {
+            let mut star: Sphere = Sphere::random(sphere_parameters, physics_parameters, rng);
+            star.radius = sphere_parameters.min_radius;
+
+            let ratio: f64 = rng.gen::<f64>().sqrt();
+            let distance: f64 = at_ratio(ratio, core.radius * 2., disk_radius);
+            let arm: f64 = (i % 2) as f64 * std::f64::consts::PI;
+            let angle: f64 = arm + ratio * arm_twist + rand_range(rng, -0.3, 0.3);
+            star.pos.z = center.z + rand_range(rng, -0.05, 0.05) * disk_radius * (1. - ratio);
+            star.color = float_to_color(1. - ratio);
+
+            stars.push((distance, angle, star));
+        }
+
+        // From the center outwards, adding up the mass inside each orbit
+        stars.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let mut inner_mass: f64 = core.mass();
+        for (distance, angle, star) in stars.iter() {
+            let orbital_speed: f64 = get_orbital_speed(inner_mass, *distance, physics_parameters);
+            v.push(star.orbiting(&center, *distance, *angle, orbital_speed));
+            inner_mass += star.mass();
+        }
+
+        Self::cancel_momentum(&mut v);
+        return v;
+    }
+
     fn from_float(
         f: f64,
         sphere_parameters: &SphereParameters,
@@ -286,15 +460,17 @@ impl Sphere {
                 sphere_parameters.min_refractivity_index,
                 sphere_parameters.max_refractivity_index,
             ),
+            normal_map: sphere_parameters.normal_map,
             is_visible: true,
+            density: sphere_parameters.density,
         };
     }
 
-    pub fn fill_vector(
+    pub fn fill_vector<R: Rng>(
         sphere_vector: &mut Vec<Sphere>,
         sphere_parameters: &SphereParameters,
         physics_parameters: &PhysicsParameters,
-        rng: &mut ThreadRng,
+        rng: &mut R,
     ) {
         match sphere_parameters.generation_mode {
             SphereGenerationMode::Hardcoded => {
@@ -313,38 +489,97 @@ impl Sphere {
                     rng,
                 ));
             }
+            SphereGenerationMode::SolarSystem => {
+                Sphere::check_orbit_physics(sphere_parameters, physics_parameters);
+                sphere_vector.extend(Sphere::solar_system_vector(
+                    &sphere_parameters,
+                    &physics_parameters,
+                    rng,
+                ));
+            }
+            SphereGenerationMode::Galaxy => {
+                Sphere::check_orbit_physics(sphere_parameters, physics_parameters);
+                sphere_vector.extend(Sphere::galaxy_vector(
+                    &sphere_parameters,
+                    &physics_parameters,
+                    rng,
+                ));
+            }
         }
     }
 
-    pub fn fill_vector_multiple_parameters(
+    // The orbits only hold with the attraction between the spheres and without the uniform gravity
+    fn check_orbit_physics(
+        sphere_parameters: &SphereParameters,
+        physics_parameters: &PhysicsParameters,
+    ) {
+        if !physics_parameters.n_body || physics_parameters.gravity.length() > 0. {
+            println!(
+                "Warning: {} needs n_body set to true and a gravity of 0 in the physics parameters, the orbits will not hold",
+                sphere_parameters.generation_mode.to_string()
+            );
+        }
+    }
+
+    pub fn fill_vector_multiple_parameters<R: Rng>(
         sphere_vector: &mut Vec<Sphere>,
         sphere_parameters_vec: &Vec<SphereParameters>,
         physics_parameters: &PhysicsParameters,
-        rng: &mut ThreadRng,
+        rng: &mut R,
     ) {
         for sphere_parameters in sphere_parameters_vec {
             Sphere::fill_vector(sphere_vector, sphere_parameters, &physics_parameters, rng);
         }
     }
 
-    pub fn physics(&mut self, physics_parameters: &PhysicsParameters) {
-        self.speed.z += physics_parameters.g;
+    // Where the sphere is at the given time offset, in seconds from now
+    pub fn get_position_at(&self, time: f64) -> Position {
+        return Position {
+            x: self.pos.x + self.speed.x * time,
+            y: self.pos.y + self.speed.y * time,
+            z: self.pos.z + self.speed.z * time,
+        };
+    }
+
+    // Proportional to the volume, the constant factor doesn't matter for the collisions
+    pub fn mass(&self) -> f64 {
+        return self.density * self.radius.powi(3);
+    }
+
+    pub fn at_time(&self, time: f64) -> Sphere {
+        let mut sphere: Sphere = *self;
+        sphere.pos = self.get_position_at(time);
+        return sphere;
+    }
+
+    // Outward unit normal at the given surface point, after applying the normal map
+    pub fn get_normal(&self, point: &Position, ray_parameters: &RayParameters) -> Position {
+        let local: Position = *point - self.pos;
+
+        return self.normal_map.perturb(
+            local.normalized(),
+            local,
+            &ray_parameters.normal_map_textures,
+        );
+    }
 
-        (self.pos.x, self.speed.x) = self.move_(
+    // Bounces off the walls of the physics box
+    pub fn bounce_on_walls(&mut self, physics_parameters: &PhysicsParameters) {
+        (self.pos.x, self.speed.x) = self.bounce(
             self.pos.x,
             self.speed.x,
             physics_parameters.min_x,
             physics_parameters.max_x,
         );
 
-        (self.pos.y, self.speed.y) = self.move_(
+        (self.pos.y, self.speed.y) = self.bounce(
             self.pos.y,
             self.speed.y,
             physics_parameters.min_y,
             physics_parameters.max_y,
         );
 
-        (self.pos.z, self.speed.z) = self.move_(
+        (self.pos.z, self.speed.z) = self.bounce(
             self.pos.z,
             self.speed.z,
             physics_parameters.min_z,
@@ -352,11 +587,10 @@ impl Sphere {
         );
     }
 
-    fn move_(&self, pos: f64, speed: f64, min: f64, max: f64) -> (f64, f64) {
+    fn bounce(&self, pos: f64, speed: f64, min: f64, max: f64) -> (f64, f64) {
         let mut new_pos: f64 = pos;
         let mut new_speed: f64 = speed;
 
-        new_pos += new_speed;
         if new_pos - self.radius < min {
             new_speed = new_speed.abs();
             new_pos = min + self.radius;
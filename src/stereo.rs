@@ -0,0 +1,101 @@
+use sdl2::pixels::Color;
+
+use crate::frame::Frame;
+
+#[derive(Clone, Copy)]
+pub enum StereoMode {
+    Off,
+    SideBySide,
+    OverUnder,
+    Anaglyph,
+}
+
+impl StereoMode {
+    pub fn to_string(&self) -> &str {
+        return match *self {
+            Self::Off => "Off",
+            Self::SideBySide => "SideBySide",
+            Self::OverUnder => "OverUnder",
+            Self::Anaglyph => "Anaglyph",
+        };
+    }
+
+    pub fn from_string(string: &str) -> Self {
+        return match string {
+            "Off" => Self::Off,
+            "SideBySide" => Self::SideBySide,
+            "OverUnder" => Self::OverUnder,
+            "Anaglyph" => Self::Anaglyph,
+            _ => Self::Off,
+        };
+    }
+
+    // Size of the combined image for eyes of the given size
+    pub fn get_output_size(&self, width: i64, height: i64) -> (i64, i64) {
+        return match *self {
+            Self::SideBySide => (width * 2, height),
+            Self::OverUnder => (width, height * 2),
+            _ => (width, height),
+        };
+    }
+}
+
+fn luminance(color: &Color) -> u8 {
+    return (0.299 * color.r as f64 + 0.587 * color.g as f64 + 0.114 * color.b as f64) as u8;
+}
+
+pub fn combine(left: &Frame, right: &Frame, mode: StereoMode) -> Frame {
+    let (width, height) = mode.get_output_size(left.width, left.height);
+    let mut frame: Frame = Frame::create_empty(width, height);
+
+    match mode {
+        StereoMode::Off => {
+            frame.colors = left.colors.clone();
+            frame.surfaces = left.surfaces.clone();
+            frame.samples = left.samples.clone();
+        }
+        StereoMode::SideBySide => {
+            frame.colors.extend(left.colors.iter());
+            frame.surfaces.extend(left.surfaces.iter());
+            right.colors.iter().for_each(|((x, y), color)| {
+                frame.colors.insert((x + left.width, *y), *color);
+            });
+            right.surfaces.iter().for_each(|((x, y), surface)| {
+                frame.surfaces.insert((x + left.width, *y), *surface);
+            });
+            frame.samples.extend(left.samples.iter());
+            right.samples.iter().for_each(|((x, y), samples)| {
+                frame.samples.insert((x + left.width, *y), *samples);
+            });
+        }
+        StereoMode::OverUnder => {
+            frame.colors.extend(left.colors.iter());
+            frame.surfaces.extend(left.surfaces.iter());
+            right.colors.iter().for_each(|((x, y), color)| {
+                frame.colors.insert((*x, y + left.height), *color);
+            });
+            right.surfaces.iter().for_each(|((x, y), surface)| {
+                frame.surfaces.insert((*x, y + left.height), *surface);
+            });
+            frame.samples.extend(left.samples.iter());
+            right.samples.iter().for_each(|((x, y), samples)| {
+                frame.samples.insert((*x, y + left.height), *samples);
+            });
+        }
+        StereoMode::Anaglyph => {
+            frame.surfaces = left.surfaces.clone();
+            frame.samples = left.samples.clone();
+
+            // Half color anaglyph: the left eye is seen in grey through the red filter
+            left.colors.iter().for_each(|(position, color)| {
+                let right_color: Color = *right.colors.get(position).unwrap_or(color);
+                frame.colors.insert(
+                    *position,
+                    Color::RGB(luminance(color), right_color.g, right_color.b),
+                );
+            });
+        }
+    }
+
+    return frame;
+}
@@ -0,0 +1,74 @@
+pub struct Texture {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f64; 3]>,
+}
+
+impl Texture {
+    pub fn load(path: &str) -> Option<Texture> {
+        println!("Reading texture {}...", path);
+
+        return match image::open(path) {
+            Ok(img) => {
+                let rgb = img.to_rgb32f();
+                Option::Some(Texture {
+                    width: rgb.width(),
+                    height: rgb.height(),
+                    pixels: rgb
+                        .pixels()
+                        .map(|p| [p[0] as f64, p[1] as f64, p[2] as f64])
+                        .collect(),
+                })
+            }
+            Err(e) => {
+                println!("Unable to read texture {}: {}", path, e);
+                Option::None
+            }
+        };
+    }
+
+    // One entry per path, a texture that failed to load stays as None so that the indices still match
+    pub fn load_vec(paths: &Vec<String>) -> Vec<Option<Texture>> {
+        return paths
+            .iter()
+            .map(|path: &String| Texture::load(path))
+            .collect();
+    }
+
+    pub fn width(&self) -> u32 {
+        return self.width;
+    }
+
+    pub fn height(&self) -> u32 {
+        return self.height;
+    }
+
+    pub fn get_pixel(&self, x: i64, y: i64) -> [f64; 3] {
+        let x: i64 = x.rem_euclid(self.width as i64);
+        let y: i64 = y.clamp(0, self.height as i64 - 1);
+        return self.pixels[(y * self.width as i64 + x) as usize];
+    }
+
+    // Bilinear sample, u wraps around and v is clamped, both in [0, 1]
+    pub fn sample(&self, u: f64, v: f64) -> [f64; 3] {
+        let x: f64 = u * self.width as f64 - 0.5;
+        let y: f64 = v * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let p00 = self.get_pixel(x0, y0);
+        let p10 = self.get_pixel(x0 + 1, y0);
+        let p01 = self.get_pixel(x0, y0 + 1);
+        let p11 = self.get_pixel(x0 + 1, y0 + 1);
+
+        let mut result: [f64; 3] = [0., 0., 0.];
+        for i in 0..3 {
+            let top: f64 = p00[i] + (p10[i] - p00[i]) * fx;
+            let bottom: f64 = p01[i] + (p11[i] - p01[i]) * fx;
+            result[i] = top + (bottom - top) * fy;
+        }
+
+        return result;
+    }
+}
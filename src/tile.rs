@@ -0,0 +1,263 @@
+use std::{sync::Arc, time::Instant};
+
+use crate::{
+    frame::{Frame, FrameAccumulator},
+    observer::Observer,
+    parameters::RayParameters,
+    ray::Ray,
+    sphere::Sphere,
+};
+
+#[derive(Clone, Copy)]
+pub enum TileOrder {
+    Scanline,
+    Spiral,
+    Hilbert,
+}
+
+impl TileOrder {
+    pub fn to_string(&self) -> &str {
+        return match *self {
+            Self::Scanline => "Scanline",
+            Self::Spiral => "Spiral",
+            Self::Hilbert => "Hilbert",
+        };
+    }
+
+    pub fn from_string(string: &str) -> Self {
+        return match string {
+            "Scanline" => Self::Scanline,
+            "Spiral" => Self::Spiral,
+            "Hilbert" => Self::Hilbert,
+            _ => Self::Spiral,
+        };
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Tile {
+    pub x: i64,
+    pub y: i64,
+    pub width: i64,
+    pub height: i64,
+}
+
+// Position of a cell along the Hilbert curve filling a size x size grid, size is a power of 2
+fn hilbert_index(size: i64, x: i64, y: i64) -> i64 {
+    let (mut x, mut y): (i64, i64) = (x, y);
+    let mut index: i64 = 0;
+    let mut s: i64 = size / 2;
+
+    while s > 0 {
+        let rx: i64 = if x & s > 0 { 1 } else { 0 };
+        let ry: i64 = if y & s > 0 { 1 } else { 0 };
+        index += s * s * ((3 * rx) ^ ry);
+
+        // Rotating the quadrant so that the curve stays continuous
+        if ry == 0 {
+            if rx == 1 {
+                x = size - 1 - x;
+                y = size - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+
+    return index;
+}
+
+// Tiles covering the image, in the order they will be rendered
+pub fn generate_tiles(width: i64, height: i64, tile_size: i64, order: TileOrder) -> Vec<Tile> {
+    let tile_size: i64 = tile_size.max(1);
+    let columns: i64 = (width + tile_size - 1) / tile_size;
+    let rows: i64 = (height + tile_size - 1) / tile_size;
+
+    let mut cells: Vec<(i64, i64)> = vec![];
+    for row in 0..rows {
+        for column in 0..columns {
+            cells.push((column, row));
+        }
+    }
+
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            // Ring by ring from the center of the image, where the interesting part usually is
+            let center_x: f64 = (columns - 1) as f64 / 2.;
+            let center_y: f64 = (rows - 1) as f64 / 2.;
+            cells.sort_by(|a: &(i64, i64), b: &(i64, i64)| {
+                let key = |(column, row): &(i64, i64)| -> (f64, f64) {
+                    let dx: f64 = *column as f64 - center_x;
+                    let dy: f64 = *row as f64 - center_y;
+                    (dx.abs().max(dy.abs()), dy.atan2(dx))
+                };
+                return key(a).partial_cmp(&key(b)).unwrap();
+            });
+        }
+        TileOrder::Hilbert => {
+            let size: i64 = (columns.max(rows) as u64).next_power_of_two() as i64;
+            cells.sort_by_key(|(column, row): &(i64, i64)| hilbert_index(size, *column, *row));
+        }
+    }
+
+    return cells
+        .iter()
+        .map(|(column, row): &(i64, i64)| Tile {
+            x: column * tile_size,
+            y: row * tile_size,
+            width: tile_size.min(width - column * tile_size),
+            height: tile_size.min(height - row * tile_size),
+        })
+        .collect();
+}
+
+// A frame being rendered tile by tile, over several iterations of the main loop
+pub struct TileJob {
+    tiles: Vec<Tile>,
+    tile_rays: Vec<Vec<usize>>,
+    next_tile: usize,
+    completed_tiles: usize,
+    cancelled: bool,
+    start: Instant,
+    // The scene as it was when the frame started, the physics keep going while the tiles are traced,
+    // shared so that a batch can borrow it while the job is updated
+    spheres: Arc<Vec<Sphere>>,
+    accumulator: FrameAccumulator,
+    pub rays_generation: u64,
+}
+
+impl TileJob {
+    pub fn new(
+        width: i64,
+        height: i64,
+        tile_size: i64,
+        order: TileOrder,
+        rays: &Vec<Ray>,
+        rays_generation: u64,
+        spheres: Vec<Sphere>,
+    ) -> TileJob {
+        let tiles: Vec<Tile> = generate_tiles(width, height, tile_size, order);
+
+        // The rays are sorted by pixel, they are split once between the tiles
+        let tile_size: i64 = tile_size.max(1);
+        let columns: i64 = (width + tile_size - 1) / tile_size;
+        let mut tile_by_cell: Vec<usize> = vec![0; tiles.len()];
+        tiles
+            .iter()
+            .enumerate()
+            .for_each(|(index, tile): (usize, &Tile)| {
+                tile_by_cell[(tile.y / tile_size * columns + tile.x / tile_size) as usize] = index;
+            });
+
+        let mut tile_rays: Vec<Vec<usize>> = vec![vec![]; tiles.len()];
+        rays.iter()
+            .enumerate()
+            .for_each(|(index, ray): (usize, &Ray)| {
+                let cell: i64 = ray.y_value / tile_size * columns + ray.x_value / tile_size;
+                tile_rays[tile_by_cell[cell as usize]].push(index);
+            });
+
+        return TileJob {
+            tiles: tiles,
+            tile_rays: tile_rays,
+            next_tile: 0,
+            completed_tiles: 0,
+            cancelled: false,
+            start: Instant::now(),
+            spheres: Arc::new(spheres),
+            accumulator: FrameAccumulator::new(width, height),
+            rays_generation: rays_generation,
+        };
+    }
+
+    // The next tiles to render with the indices of their rays, at most count of them
+    pub fn take_tiles(&mut self, count: usize) -> Vec<(Tile, &Vec<usize>)> {
+        let end: usize = (self.next_tile + count).min(self.tiles.len());
+        let start: usize = self.next_tile;
+        self.next_tile = end;
+
+        return (start..end)
+            .map(|index: usize| (self.tiles[index], &self.tile_rays[index]))
+            .collect();
+    }
+
+    // Batches of one tile per worker of the pool, until batch_time_ms is spent, returns the new tiles
+    pub fn render_batch(
+        &mut self,
+        observer: &Observer,
+        ray_parameters: &RayParameters,
+        batch_time_ms: f64,
+    ) -> Vec<(Tile, Frame)> {
+        let start: Instant = Instant::now();
+        let mut rendered: Vec<(Tile, Frame)> = vec![];
+        let spheres: Arc<Vec<Sphere>> = Arc::clone(&self.spheres);
+        let sphere_vector: Vec<&Sphere> = spheres.iter().collect();
+
+        while self.is_running() && start.elapsed().as_secs_f64() * 1000. < batch_time_ms {
+            let (tiles, accumulators): (Vec<Tile>, Vec<FrameAccumulator>) = {
+                let batch: Vec<(Tile, &Vec<usize>)> = self.take_tiles(rayon::current_num_threads());
+                (
+                    batch.iter().map(|(tile, _)| *tile).collect(),
+                    observer.trace_tiles(&batch, ray_parameters, &sphere_vector),
+                )
+            };
+
+            tiles.into_iter().zip(accumulators.into_iter()).for_each(
+                |(tile, accumulator): (Tile, FrameAccumulator)| {
+                    let tile_frame: Frame = self.add_tile(&tile, accumulator);
+                    rendered.push((tile, tile_frame));
+                },
+            );
+        }
+
+        return rendered;
+    }
+
+    // Adds the samples of a rendered tile, including the ones splatted on its neighbours, and returns
+    // its pixels as they are so far, the pixels on its border change again with the next tiles
+    fn add_tile(&mut self, tile: &Tile, accumulator: FrameAccumulator) -> Frame {
+        self.accumulator.merge(accumulator);
+        self.completed_tiles += 1;
+
+        return self
+            .accumulator
+            .get_region(tile.x, tile.y, tile.width, tile.height);
+    }
+
+    // The whole frame, once all the tiles are done
+    pub fn get_frame(&self) -> Frame {
+        return self.accumulator.get_frame();
+    }
+
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+        println!("Render cancelled at {:.0}%", self.get_progress() * 100.);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.completed_tiles >= self.tiles.len();
+    }
+
+    pub fn is_running(&self) -> bool {
+        return !self.cancelled && !self.is_finished();
+    }
+
+    pub fn get_progress(&self) -> f64 {
+        return self.completed_tiles as f64 / self.tiles.len().max(1) as f64;
+    }
+
+    pub fn get_elapsed_seconds(&self) -> f64 {
+        return self.start.elapsed().as_secs_f64();
+    }
+
+    // Remaining time if the next tiles go as fast as the previous ones
+    pub fn get_eta_seconds(&self) -> f64 {
+        let progress: f64 = self.get_progress();
+        if progress <= 0. {
+            return 0.;
+        }
+
+        return self.get_elapsed_seconds() * (1. - progress) / progress;
+    }
+}
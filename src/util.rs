@@ -1,7 +1,7 @@
-use rand::{distributions::uniform::SampleBorrow, Rng};
+use rand::{distributions::uniform::SampleBorrow, rngs::SmallRng, Rng, SeedableRng};
 use sdl2::pixels::Color;
 
-pub fn rand_range<T>(rng: &mut rand::prelude::ThreadRng, low: T, high: T) -> T
+pub fn rand_range<T, R: Rng>(rng: &mut R, low: T, high: T) -> T
 where
     T: std::cmp::PartialEq + SampleBorrow<T> + rand::distributions::uniform::SampleUniform,
 {
@@ -12,11 +12,27 @@ where
     };
 }
 
+// Mixes a value into a seed, close values give unrelated seeds (splitmix64)
+pub fn mix_seed(seed: u64, value: u64) -> u64 {
+    let mut z: u64 = seed ^ value.wrapping_mul(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    return z ^ (z >> 31);
+}
+
+// Seeded for the reproducible renders, random otherwise
+pub fn get_rng(seed: Option<u64>) -> SmallRng {
+    return match seed {
+        Some(seed) => SmallRng::seed_from_u64(seed),
+        None => SmallRng::from_rng(rand::thread_rng()).unwrap(),
+    };
+}
+
 pub fn float_to_color(f: f64) -> Color {
     return Color::RGB((f * 255.) as u8, 0, ((1. - f) * 255.) as u8);
 }
 
-pub fn rand_color(rng: &mut rand::prelude::ThreadRng) -> Color {
+pub fn rand_color<R: Rng>(rng: &mut R) -> Color {
     return Color::RGB(
         rng.gen_range(0, 255),
         rng.gen_range(0, 255),